@@ -0,0 +1,365 @@
+use std::cell::Cell;
+use std::time::{Duration, Instant};
+
+use crate::ai::{as_bitboard, Agent, Position};
+use crate::direction::{Direction, DirectionSet};
+use crate::field::Field;
+use crate::spawn::WeightedSpawn;
+
+/// Weights of the parts of the static evaluation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HeuristicWeights {
+  /// Per empty cell, obstacles are neither empty nor tiles
+  pub empty: f64,
+  /// Penalty for rows and columns that go up and down, in levels (log2 of numbers).
+  /// Obstacles split them as they split the lines of a push
+  pub monotonicity: f64,
+  /// Penalty for level differences between neighbours, diagonal ones included
+  pub smoothness: f64,
+  /// Per pair of equal neighbours
+  pub merges: f64,
+  /// Bonus times the biggest level when it sits in a corner
+  pub max_in_corner: f64,
+}
+
+impl Default for HeuristicWeights {
+  fn default() -> Self {
+    HeuristicWeights {
+      empty: 2.7,
+      monotonicity: 1.0,
+      smoothness: 0.1,
+      merges: 0.7,
+      max_in_corner: 1.0,
+    }
+  }
+}
+
+impl HeuristicWeights {
+  pub fn evaluate<P: Position>(&self, position: &P) -> f64 {
+    let (width, height) = position.get_size();
+    let mut empty = 0.0;
+    let mut smoothness = 0.0;
+    let mut merges = 0.0;
+    let mut max_level = (0.0, false);
+
+    for y in 0 .. height {
+      for x in 0 .. width {
+        if position.is_obstacle(x, y) {
+          continue;
+        }
+        let level = position.get_level(x, y);
+        if level == 0.0 {
+          empty += 1.0;
+          continue;
+        }
+
+        let corner = (x == 0 || x == width - 1) && (y == 0 || y == height - 1);
+        if level > max_level.0 || (level == max_level.0 && corner) {
+          max_level = (level, corner);
+        }
+
+        for (dx, dy) in [(1, 0), (0, 1), (1, 1), (-1, 1)].iter() {
+          let x2 = x as i32 + dx;
+          let y2 = y as i32 + dy;
+          if x2 < 0 || x2 >= width as i32 || y2 >= height as i32
+            || position.is_obstacle(x2 as usize, y2 as usize) {
+            continue;
+          }
+
+          let other = position.get_level(x2 as usize, y2 as usize);
+          if other != 0.0 {
+            smoothness -= (level - other).abs();
+            if level == other {
+              merges += 1.0;
+            }
+          }
+        }
+      }
+    }
+
+    let mut monotonicity = 0.0;
+    // `None` for an obstacle, which ends one part of the line and starts another
+    let mut add_line = |levels: &mut dyn Iterator<Item = Option<f64>>| {
+      let (mut up, mut down) = (0.0, 0.0);
+      let mut previous: Option<f64> = None;
+      for level in levels {
+        match (previous, level) {
+          (Some(p), Some(level)) => if level > p { up += level - p; } else { down += p - level; },
+          (_, None) => {
+            monotonicity -= f64::min(up, down);
+            (up, down) = (0.0, 0.0);
+          }
+          _ => {}
+        }
+        previous = level;
+      }
+      monotonicity -= f64::min(up, down);
+    };
+    let level = |x, y| if position.is_obstacle(x, y) { None } else { Some(position.get_level(x, y)) };
+    for y in 0 .. height {
+      add_line(&mut (0 .. width).map(|x| level(x, y)));
+    }
+    for x in 0 .. width {
+      add_line(&mut (0 .. height).map(|y| level(x, y)));
+    }
+
+    let corner = if max_level.1 { max_level.0 } else { 0.0 };
+
+    self.empty * empty + self.monotonicity * monotonicity + self.smoothness * smoothness
+      + self.merges * merges + self.max_in_corner * corner
+  }
+}
+
+/// Expectimax search: max nodes over the allowed directions,
+/// chance nodes over the cells and numbers the spawn rules can put after the move.
+/// With several tiles per move they are placed one after another, as the spawn rules do
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expectimax {
+  /// Moves of the player to look ahead
+  pub depth: usize,
+  /// Chance branches less likely than this are evaluated statically
+  pub min_probability: f64,
+  /// With a limit the search deepens one move at a time up to `depth`
+  /// and keeps the deepest search finished in time, `None` always searches `depth`
+  pub time_limit: Option<Duration>,
+  pub weights: HeuristicWeights,
+}
+
+impl Default for Expectimax {
+  fn default() -> Self {
+    Expectimax {depth: 2, min_probability: 0.0001, time_limit: None, weights: Default::default()}
+  }
+}
+
+struct Context<'i> {
+  directions: DirectionSet,
+  spawn: &'i WeightedSpawn,
+  numbers: &'i [(i32, f64)],
+  deadline: Option<Instant>,
+  /// Set when the deadline passes, the values found are not to be used then
+  timed_out: Cell<bool>,
+}
+
+impl Expectimax {
+  pub fn new(depth: usize) -> Self {
+    Expectimax {depth, ..Default::default()}
+  }
+
+  fn evaluate_position<P: Position>(&self, position: &P, field: &Field) -> Vec<(Direction, f64)> {
+    let time_limit = match self.time_limit {
+      Some(time_limit) => time_limit,
+      None => return self.search(position, field, self.depth, None).unwrap(),
+    };
+
+    // One move ahead is cheap, it gives a result however short the limit is
+    let deadline = Instant::now() + time_limit;
+    let mut result = self.search(position, field, 1, None).unwrap();
+    for depth in 2 ..= self.depth {
+      match self.search(position, field, depth, Some(deadline)) {
+        Some(values) => result = values,
+        None => break,
+      }
+    }
+    result
+  }
+
+  /// Values of the moves `depth` moves ahead, `None` if the deadline passes first
+  fn search<P: Position>(
+    &self,
+    position: &P,
+    field: &Field,
+    depth: usize,
+    deadline: Option<Instant>
+  ) -> Option<Vec<(Direction, f64)>> {
+    let rules = field.get_rules();
+    let numbers = rules.spawn.get_distribution(field);
+    let context = Context {
+      directions: rules.directions,
+      spawn: &rules.spawn,
+      numbers: &numbers,
+      deadline,
+      timed_out: Cell::new(false),
+    };
+    let values = context.directions.iter().filter_map(|dir| {
+      position.push(dir).map(|(next, _)| {
+        (dir, self.chance(&next, &context, dir, depth.max(1) - 1, 1.0))
+      })
+    }).collect();
+    if context.timed_out.get() {
+      None
+    } else {
+      Some(values)
+    }
+  }
+
+  fn max<P: Position>(&self, position: &P, context: &Context, depth: usize, probability: f64) -> f64 {
+    context.directions.iter()
+      .filter_map(|dir| position.push(dir).map(|(next, _)| (dir, next)))
+      .map(|(dir, next)| self.chance(&next, context, dir, depth - 1, probability))
+      .fold(None, |best: Option<f64>, value| Some(best.map_or(value, |b| b.max(value))))
+      // No moves left is the worst outcome
+      .unwrap_or(-1e9)
+  }
+
+  fn chance<P: Position>(
+    &self,
+    position: &P,
+    context: &Context,
+    last_move: Direction,
+    depth: usize,
+    probability: f64
+  ) -> f64 {
+    if depth == 0 || position.get_empty_cells().is_empty() {
+      return self.weights.evaluate(position);
+    }
+    if context.timed_out.get() || context.deadline.is_some_and(|d| Instant::now() >= d) {
+      context.timed_out.set(true);
+      return 0.0;
+    }
+    self.spawn(position, context, last_move, context.spawn.count, depth, probability)
+  }
+
+  /// Averages over the cells and numbers of the `tiles` still to spawn, then the player moves
+  fn spawn<P: Position>(
+    &self,
+    position: &P,
+    context: &Context,
+    last_move: Direction,
+    tiles: usize,
+    depth: usize,
+    probability: f64
+  ) -> f64 {
+    let empty = position.get_empty_cells();
+    if tiles == 0 || empty.is_empty() {
+      return self.max(position, context, depth, probability);
+    }
+    if probability < self.min_probability {
+      return self.weights.evaluate(position);
+    }
+
+    let cells = context.spawn.candidates(position.get_size(), &empty, Some(last_move));
+    let cell_probability = 1.0 / cells.len() as f64;
+    let mut result = 0.0;
+    for (x, y) in cells {
+      for (number, number_probability) in context.numbers {
+        let p = cell_probability * number_probability;
+        let next = position.with_number(x, y, *number);
+        result += p * self.spawn(&next, context, last_move, tiles - 1, depth, probability * p);
+      }
+    }
+    result
+  }
+}
+
+impl Agent for Expectimax {
+  fn evaluate(&mut self, field: &Field) -> Vec<(Direction, f64)> {
+    match as_bitboard(field) {
+      Some(board) => self.evaluate_position(&board, field),
+      None => self.evaluate_position(field, field),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::merge::MergeRules;
+  use crate::rules::Rules;
+  use crate::spawn::SpawnLocation;
+
+  #[test]
+  fn evaluates_legal_moves_only() {
+    let field = Field::from_numbers(vec![
+      vec![2, 4, 8, 16], vec![4, 8, 16, 32], vec![8, 16, 32, 64], vec![16, 32, 64, 0]
+    ]);
+    let mut agent = Expectimax::default();
+    let moves: DirectionSet = agent.evaluate(&field).iter().map(|(d, _)| *d).collect();
+    assert_eq!(moves, field.legal_moves());
+    assert!(agent.best_move(&field).is_some());
+  }
+
+  #[test]
+  fn bitboard_and_field_agree() {
+    let field = Field::from_numbers(vec![
+      vec![0, 2, 0, 4], vec![2, 0, 8, 0], vec![0, 0, 4, 0], vec![16, 0, 0, 2]
+    ]);
+    let agent = Expectimax::new(2);
+    let board = as_bitboard(&field).unwrap();
+    let slow = agent.evaluate_position(&field, &field);
+    let fast = agent.evaluate_position(&board, &field);
+    assert_eq!(slow.len(), fast.len());
+    for ((d1, v1), (d2, v2)) in slow.iter().zip(fast.iter()) {
+      assert_eq!(d1, d2);
+      assert!((v1 - v2).abs() < 1e-9);
+    }
+  }
+
+  #[test]
+  fn plays_other_rules() {
+    let mut field = Field::from_numbers(vec![vec![1, 2, 0], vec![0, 3, 0], vec![0, 0, 5]]);
+    field.set_rules(Rules {merge_rule: MergeRules::Fibonacci, ..Default::default()});
+    assert!(Expectimax::new(2).best_move(&field).is_some());
+  }
+
+  #[test]
+  fn obstacles_are_not_empty() {
+    let open = Field::from_numbers(vec![vec![2, 0, 4], vec![0, 8, 0], vec![4, 0, 2]]);
+    let mut blocked = open.clone();
+    blocked.set_rules(Rules {obstacles: vec![(1, 0), (0, 1)], ..Default::default()});
+    let weights = HeuristicWeights {empty: 1.0, monotonicity: 0.0, smoothness: 0.0, merges: 0.0, max_in_corner: 0.0};
+    assert_eq!(weights.evaluate(&open) - weights.evaluate(&blocked), 2.0);
+    // Levels on both sides of an obstacle are not compared
+    let weights = HeuristicWeights {empty: 0.0, monotonicity: 1.0, ..weights};
+    let mut line = Field::from_numbers(vec![vec![8, 0, 2, 8]]);
+    assert_eq!(weights.evaluate(&line), -3.0);
+    line.set_rules(Rules {obstacles: vec![(1, 0)], ..Default::default()});
+    assert_eq!(weights.evaluate(&line), 0.0);
+  }
+
+  #[test]
+  fn spawns_where_the_rules_put_tiles() {
+    let agent = Expectimax::new(2);
+    let best_push = |position: &Field| -> f64 {
+      DirectionSet::default().iter()
+        .filter_map(|dir| Position::push(position, dir))
+        .map(|(next, _)| agent.weights.evaluate(&next))
+        .fold(-1e9, f64::max)
+    };
+    let value = |field: &Field, dir: Direction| -> f64 {
+      agent.evaluate_position(field, field).iter().find(|(d, _)| *d == dir).unwrap().1
+    };
+
+    let mut field = Field::from_numbers(vec![vec![0, 2, 0], vec![0, 4, 0], vec![0, 0, 0]]);
+    let spawn = WeightedSpawn {weights: vec![(2, 1)], location: SpawnLocation::CornersFirst, ..Default::default()};
+    field.set_rules(Rules {spawn, ..Default::default()});
+    // After Left the empty corners are (2, 0), (0, 2) and (2, 2)
+    let (pushed, _) = Position::push(&field, Direction::Left).unwrap();
+    let corners = [(2, 0), (0, 2), (2, 2)];
+    let expected = corners.iter().map(|(x, y)| best_push(&pushed.with_number(*x, *y, 2))).sum::<f64>() / 3.0;
+    assert!((value(&field, Direction::Left) - expected).abs() < 1e-9);
+
+    // Three tiles fill the board whatever the order
+    let mut field = Field::from_numbers(vec![vec![0, 2], vec![0, 0]]);
+    let spawn = WeightedSpawn {weights: vec![(2, 1)], count: 3, ..Default::default()};
+    field.set_rules(Rules {spawn, ..Default::default()});
+    let full = Field::from_numbers(vec![vec![2, 2], vec![2, 2]]);
+    assert!((value(&field, Direction::Left) - best_push(&full)).abs() < 1e-9);
+  }
+
+  #[test]
+  fn time_limit() {
+    let field = Field::from_numbers(vec![
+      vec![0, 2, 0, 4], vec![2, 0, 8, 0], vec![0, 0, 4, 0], vec![16, 0, 0, 2]
+    ]);
+    let hurried = Expectimax {depth: 5, time_limit: Some(Duration::ZERO), ..Default::default()};
+    assert_eq!(hurried.evaluate_position(&field, &field), Expectimax::new(1).evaluate_position(&field, &field));
+    let patient = Expectimax {time_limit: Some(Duration::from_secs(60)), ..Default::default()};
+    assert_eq!(patient.evaluate_position(&field, &field), Expectimax::default().evaluate_position(&field, &field));
+  }
+
+  #[test]
+  fn no_moves_no_result() {
+    let field = Field::from_numbers(vec![vec![2, 4], vec![8, 16]]);
+    assert_eq!(Expectimax::default().best_move(&field), None);
+  }
+}
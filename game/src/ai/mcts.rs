@@ -0,0 +1,210 @@
+use std::time::{Duration, Instant};
+
+use crate::ai::{as_bitboard, push_random, spawn_by_rules, Agent, Position};
+use crate::direction::{Direction, DirectionSet};
+use crate::field::Field;
+use crate::rng::GameRng;
+use crate::rules::Rules;
+
+/// When the search stops
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Budget {
+  Iterations(usize),
+  Time(Duration),
+}
+
+/// Open loop Monte Carlo tree search: nodes stand for sequences of moves,
+/// every iteration samples fresh spawns of the rules along the path and finishes with a random rollout.
+/// The value of a move is the average score collected after it
+#[derive(Debug, Clone)]
+pub struct Mcts {
+  pub budget: Budget,
+  /// UCB1 exploration constant, values are normalized to 0 .. 1 before it applies
+  pub exploration: f64,
+  /// Random moves per rollout, `None` to play until the game is over
+  pub rollout_depth: Option<usize>,
+  rng: GameRng,
+}
+
+#[derive(Debug, Default, Clone)]
+struct Node {
+  visits: u32,
+  total: f64,
+  children: [Option<usize>; 8],
+}
+
+struct Context<'i> {
+  directions: DirectionSet,
+  rules: &'i Rules,
+}
+
+impl Mcts {
+  pub fn new(budget: Budget, seed: u64) -> Self {
+    Mcts {budget, exploration: 1.4, rollout_depth: None, rng: GameRng::new(seed)}
+  }
+
+  fn search<P: Position>(&mut self, root: &P, field: &Field) -> Vec<(Direction, f64)> {
+    let context = Context {directions: field.get_rules().directions, rules: field.get_rules()};
+    let legal: DirectionSet = context.directions.iter().filter(|d| root.push(*d).is_some()).collect();
+    if legal.is_empty() {
+      return Vec::new();
+    }
+
+    let mut nodes = vec![Node::default()];
+    let mut max_value: f64 = 1.0;
+    let start = Instant::now();
+    let mut iteration = 0;
+    loop {
+      let done = match self.budget {
+        Budget::Iterations(n) => iteration >= n,
+        Budget::Time(t) => iteration > 0 && start.elapsed() >= t,
+      };
+      if done {
+        break;
+      }
+      iteration += 1;
+
+      let value = self.iterate(root, &context, &mut nodes, max_value);
+      max_value = max_value.max(value);
+    }
+
+    legal.iter().map(|dir| {
+      let value = match nodes[0].children[dir.get_index()] {
+        Some(child) if nodes[child].visits > 0 => nodes[child].total / nodes[child].visits as f64,
+        _ => 0.0,
+      };
+      (dir, value)
+    }).collect()
+  }
+
+  /// One selection, expansion, rollout and backpropagation, returns the collected score
+  fn iterate<P: Position>(
+    &mut self,
+    root: &P,
+    context: &Context,
+    nodes: &mut Vec<Node>,
+    max_value: f64
+  ) -> f64 {
+    let mut path = vec![0];
+    let mut position = root.clone();
+    let mut value = 0.0;
+
+    loop {
+      let node = *path.last().unwrap();
+      let mut moves: Vec<(Direction, P, u64)> = context.directions.iter()
+        .filter_map(|dir| position.push(dir).map(|(next, score)| (dir, next, score)))
+        .collect();
+      if moves.is_empty() {
+        break;
+      }
+
+      let unexplored = moves.iter().position(|(dir, _, _)| nodes[node].children[dir.get_index()].is_none());
+      let (dir, next, score, expanded) = match unexplored {
+        Some(k) => {
+          let (dir, next, score) = moves.swap_remove(k);
+          (dir, next, score, true)
+        }
+        None => {
+          let parent_visits = nodes[node].visits.max(1) as f64;
+          let ucb = |child: &Node| {
+            let mean = child.total / child.visits.max(1) as f64 / max_value;
+            mean + self.exploration * (parent_visits.ln() / child.visits.max(1) as f64).sqrt()
+          };
+          let k = (0 .. moves.len()).max_by(|a, b| {
+            let a = ucb(&nodes[nodes[node].children[moves[*a].0.get_index()].unwrap()]);
+            let b = ucb(&nodes[nodes[node].children[moves[*b].0.get_index()].unwrap()]);
+            a.partial_cmp(&b).unwrap()
+          }).unwrap();
+          let (dir, next, score) = moves.swap_remove(k);
+          (dir, next, score, false)
+        }
+      };
+
+      let child = match nodes[node].children[dir.get_index()] {
+        Some(child) => child,
+        None => {
+          nodes.push(Node::default());
+          nodes[node].children[dir.get_index()] = Some(nodes.len() - 1);
+          nodes.len() - 1
+        }
+      };
+      path.push(child);
+      value += score as f64;
+      position = match spawn_by_rules(&next, context.rules, Some(dir), &mut self.rng) {
+        Some(p) => p,
+        None => next,
+      };
+
+      if expanded {
+        break;
+      }
+    }
+
+    value += self.rollout(position, context);
+    for node in path {
+      nodes[node].visits += 1;
+      nodes[node].total += value;
+    }
+    value
+  }
+
+  fn rollout<P: Position>(&mut self, mut position: P, context: &Context) -> f64 {
+    let mut value = 0.0;
+    let mut steps = 0;
+    while self.rollout_depth.is_none_or(|depth| steps < depth) {
+      match push_random(&position, context.directions, &mut self.rng) {
+        Some((dir, next, score)) => {
+          value += score as f64;
+          position = spawn_by_rules(&next, context.rules, Some(dir), &mut self.rng).unwrap_or(next);
+        }
+        None => break,
+      }
+      steps += 1;
+    }
+    value
+  }
+}
+
+impl Agent for Mcts {
+  fn evaluate(&mut self, field: &Field) -> Vec<(Direction, f64)> {
+    match as_bitboard(field) {
+      Some(board) => self.search(&board, field),
+      None => self.search(field, field),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn field() -> Field {
+    Field::from_numbers(vec![
+      vec![0, 2, 0, 4], vec![2, 0, 8, 0], vec![0, 0, 4, 0], vec![16, 0, 0, 2]
+    ])
+  }
+
+  #[test]
+  fn evaluates_legal_moves() {
+    let field = field();
+    let mut agent = Mcts::new(Budget::Iterations(200), 1);
+    let moves: DirectionSet = agent.evaluate(&field).iter().map(|(d, _)| *d).collect();
+    assert_eq!(moves, field.legal_moves());
+  }
+
+  #[test]
+  fn same_seed_same_choice() {
+    let field = field();
+    let first = Mcts::new(Budget::Iterations(100), 7).evaluate(&field);
+    let second = Mcts::new(Budget::Iterations(100), 7).evaluate(&field);
+    assert_eq!(first, second);
+  }
+
+  #[test]
+  fn time_budget_and_other_sizes() {
+    let field = Field::from_numbers(vec![vec![2, 0, 4, 0, 2], vec![0, 8, 0, 2, 0], vec![4, 0, 0, 0, 2]]);
+    let mut agent = Mcts::new(Budget::Time(Duration::from_millis(20)), 1);
+    agent.rollout_depth = Some(20);
+    assert!(agent.best_move(&field).is_some());
+  }
+}
@@ -0,0 +1,228 @@
+use rand::RngCore;
+use smallvec::SmallVec;
+
+use crate::bitboard::{Bitboard, BITBOARD_SIZE};
+use crate::direction::{Direction, DirectionSet};
+use crate::field::Field;
+use crate::merge::MergeRule;
+use crate::rng::{gen_below, GameRng};
+use crate::rules::Rules;
+
+pub mod expectimax;
+pub mod mcts;
+pub mod ntuple;
+
+/// A player that picks moves for a field
+pub trait Agent {
+  /// Value of every legal move, bigger is better
+  fn evaluate(&mut self, field: &Field) -> Vec<(Direction, f64)>;
+
+  fn best_move(&mut self, field: &Field) -> Option<Direction> {
+    best_of(&self.evaluate(field))
+  }
+}
+
+/// The move with the biggest value, the first one of equal moves
+pub fn best_of(values: &[(Direction, f64)]) -> Option<Direction> {
+  values.iter()
+    .fold(None, |best: Option<(Direction, f64)>, (dir, value)| match best {
+      Some((_, best_value)) if best_value >= *value => best,
+      _ => Some((*dir, *value)),
+    })
+    .map(|(dir, _)| dir)
+}
+
+/// What a search needs from a field, implemented by `Field` for any rules
+/// and by `Bitboard` for the fast 4x4 case
+pub trait Position: Clone {
+  fn get_size(&self) -> (usize, usize);
+
+  fn get_number(&self, x: usize, y: usize) -> i32;
+
+  /// log2 of the number, 0 for an empty cell
+  fn get_level(&self, x: usize, y: usize) -> f64 {
+    match self.get_number(x, y) {
+      0 => 0.0,
+      n => (n as f64).log2(),
+    }
+  }
+
+  /// The position after the push and the score for it, `None` if nothing moved
+  fn push(&self, dir: Direction) -> Option<(Self, u64)>;
+
+  fn get_empty_cells(&self) -> Vec<(usize, usize)>;
+
+  /// Cells that never hold a tile, see `Rules::obstacles`
+  fn is_obstacle(&self, _x: usize, _y: usize) -> bool {
+    false
+  }
+
+  fn with_number(&self, x: usize, y: usize, number: i32) -> Self;
+}
+
+impl Position for Field {
+  fn get_size(&self) -> (usize, usize) {
+    Field::get_size(self)
+  }
+
+  fn get_number(&self, x: usize, y: usize) -> i32 {
+    self.get_numbers()[y][x]
+  }
+
+  fn is_obstacle(&self, x: usize, y: usize) -> bool {
+    self.get_rules().is_obstacle((x, y))
+  }
+
+  fn push(&self, dir: Direction) -> Option<(Self, u64)> {
+    let mut result = self.clone();
+    let events = result.push_dir(dir);
+    if events.is_empty() {
+      None
+    } else {
+      Some((result, events.iter().map(|e| e.get_score()).sum()))
+    }
+  }
+
+  fn get_empty_cells(&self) -> Vec<(usize, usize)> {
+    Field::get_empty_cells(self)
+  }
+
+  fn with_number(&self, x: usize, y: usize, number: i32) -> Self {
+    let mut result = self.clone();
+    result.get_numbers_mut()[y][x] = number;
+    result
+  }
+}
+
+impl Position for Bitboard {
+  fn get_size(&self) -> (usize, usize) {
+    (BITBOARD_SIZE, BITBOARD_SIZE)
+  }
+
+  fn get_number(&self, x: usize, y: usize) -> i32 {
+    Bitboard::get_number(self, x, y)
+  }
+
+  fn get_level(&self, x: usize, y: usize) -> f64 {
+    self.get_exponent(x, y) as f64
+  }
+
+  fn push(&self, dir: Direction) -> Option<(Self, u64)> {
+    let (result, score) = Bitboard::push(self, dir);
+    if result == *self {
+      None
+    } else {
+      Some((result, score))
+    }
+  }
+
+  fn get_empty_cells(&self) -> Vec<(usize, usize)> {
+    let mask = self.get_empty_mask();
+    (0 .. BITBOARD_SIZE * BITBOARD_SIZE)
+      .filter(|c| mask & (1 << c) != 0)
+      .map(|c| (c % BITBOARD_SIZE, c / BITBOARD_SIZE))
+      .collect()
+  }
+
+  fn with_number(&self, x: usize, y: usize, number: i32) -> Self {
+    let mut result = *self;
+    result.set_exponent(x, y, number.trailing_zeros() as u8);
+    result
+  }
+}
+
+/// Bitboard for the field if its rules and spawned numbers fit one
+pub fn as_bitboard(field: &Field) -> Option<Bitboard> {
+  let fits = field.get_rules().spawn.get_distribution(field).iter()
+    .all(|(n, _)| *n >= 2 && n & (n - 1) == 0 && n.trailing_zeros() <= crate::bitboard::MAX_EXPONENT as u32);
+  if fits {
+    Bitboard::from_field(field)
+  } else {
+    None
+  }
+}
+
+/// Adds the tiles the spawn rules add after `last_move`: as many, in the same cells
+/// and with the same numbers as on a field. `None` if there is no empty cell
+pub fn spawn_by_rules<P: Position, R: RngCore + ?Sized>(
+  position: &P,
+  rules: &Rules,
+  last_move: Option<Direction>,
+  rng: &mut R
+) -> Option<P> {
+  let cells = position.get_empty_cells();
+  if cells.is_empty() {
+    return None;
+  }
+
+  let items = rules.spawn.place(position.get_size(), cells, rules.merge_rule.start_numbers(), last_move, rng);
+  Some(items.iter().fold(position.clone(), |result, (x, y, number)| result.with_number(*x, *y, *number)))
+}
+
+/// Pushes to a random direction that changes the position
+pub fn push_random<P: Position, R: RngCore + ?Sized>(
+  position: &P,
+  directions: DirectionSet,
+  rng: &mut R
+) -> Option<(Direction, P, u64)> {
+  let mut candidates: SmallVec<[Direction; 8]> = directions.iter().collect();
+  while !candidates.is_empty() {
+    let k = gen_below(rng, candidates.len());
+    if let Some((next, score)) = position.push(candidates[k]) {
+      return Some((candidates[k], next, score));
+    }
+    candidates.swap_remove(k);
+  }
+
+  None
+}
+
+/// Plays uniformly random legal moves, the baseline to compare agents with
+#[derive(Debug, Clone)]
+pub struct RandomAgent {
+  rng: GameRng,
+}
+
+impl RandomAgent {
+  pub fn new(seed: u64) -> Self {
+    RandomAgent {rng: GameRng::new(seed)}
+  }
+}
+
+impl Agent for RandomAgent {
+  fn evaluate(&mut self, field: &Field) -> Vec<(Direction, f64)> {
+    field.legal_moves().iter().map(|dir| (dir, 0.0)).collect()
+  }
+
+  fn best_move(&mut self, field: &Field) -> Option<Direction> {
+    let moves: SmallVec<[Direction; 8]> = field.legal_moves().iter().collect();
+    if moves.is_empty() {
+      None
+    } else {
+      Some(moves[gen_below(&mut self.rng, moves.len())])
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::spawn::{SpawnLocation, WeightedSpawn};
+
+  #[test]
+  fn spawns_as_on_the_field() {
+    let spawn = WeightedSpawn::new(vec![(2, 1), (8, 3)], 2, SpawnLocation::AwayFromMove).unwrap();
+    let mut field = Field::with_rules(4, 4, Rules {spawn, ..Default::default()});
+    field.get_numbers_mut()[1][2] = 4;
+    let board = as_bitboard(&field).unwrap();
+    let rules = field.get_rules().clone();
+    for seed in 0 .. 20 {
+      let mut expected = field.clone();
+      expected.add_item(Some(Direction::Left), &mut GameRng::new(seed));
+      let spawned = spawn_by_rules(&board, &rules, Some(Direction::Left), &mut GameRng::new(seed)).unwrap();
+      assert_eq!(&spawned.get_numbers(), expected.get_numbers());
+      let spawned = spawn_by_rules(&field, &rules, Some(Direction::Left), &mut GameRng::new(seed)).unwrap();
+      assert_eq!(spawned.get_numbers(), expected.get_numbers());
+    }
+  }
+}
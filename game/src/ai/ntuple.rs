@@ -0,0 +1,366 @@
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use rand::RngCore;
+
+use crate::ai::{as_bitboard, push_random, spawn_by_rules, Agent, Position};
+use crate::bitboard::{Bitboard, BITBOARD_SIZE};
+use crate::direction::{Direction, DirectionSet};
+use crate::field::Field;
+use crate::rng::GameRng;
+use crate::rules::Rules;
+
+/// First bytes of a weights file
+pub const NTUPLE_MAGIC: [u8; 8] = *b"2048NTUP";
+/// Version of the weights file layout, bumped on every incompatible change
+pub const NTUPLE_VERSION: u32 = 1;
+/// Longest tuple, a tuple of n cells keeps 16^n weights
+pub const MAX_TUPLE_LENGTH: usize = 6;
+
+const CELLS: usize = BITBOARD_SIZE * BITBOARD_SIZE;
+
+/// Value function of a 4x4 bitboard: a sum of weights looked up by the exponents
+/// in groups of cells (tuples), every tuple taken in all eight symmetries of the board
+#[derive(Debug, Clone, PartialEq)]
+pub struct NTupleNetwork {
+  tuples: Vec<Vec<u8>>,
+  /// Cells of every tuple in every symmetry
+  symmetric: Vec<Vec<Vec<u8>>>,
+  weights: Vec<Vec<f32>>,
+}
+
+/// Rows, squares and the main diagonal, small enough to learn in minutes
+pub fn default_tuples() -> Vec<Vec<u8>> {
+  vec![
+    vec![0, 1, 2, 3],
+    vec![4, 5, 6, 7],
+    vec![0, 1, 4, 5],
+    vec![5, 6, 9, 10],
+    vec![0, 5, 10, 15],
+  ]
+}
+
+/// Cell index `y * 4 + x` under each of the eight rotations and reflections
+fn symmetries(cell: u8) -> [u8; 8] {
+  let last = BITBOARD_SIZE as u8 - 1;
+  let (x, y) = (cell % BITBOARD_SIZE as u8, cell / BITBOARD_SIZE as u8);
+  let index = |x: u8, y: u8| y * BITBOARD_SIZE as u8 + x;
+  [
+    index(x, y), index(last - x, y), index(x, last - y), index(last - x, last - y),
+    index(y, x), index(last - y, x), index(y, last - x), index(last - y, last - x),
+  ]
+}
+
+impl Default for NTupleNetwork {
+  fn default() -> Self {
+    NTupleNetwork::new(default_tuples()).unwrap()
+  }
+}
+
+impl NTupleNetwork {
+  /// Network with zero weights, `None` if a tuple is empty, too long or has cells out of the board
+  pub fn new(tuples: Vec<Vec<u8>>) -> Option<Self> {
+    let valid = tuples.iter().all(|t| {
+      !t.is_empty() && t.len() <= MAX_TUPLE_LENGTH && t.iter().all(|c| (*c as usize) < CELLS)
+    });
+    if !valid {
+      return None;
+    }
+
+    let symmetric = tuples.iter().map(|tuple| {
+      (0 .. 8).map(|s| tuple.iter().map(|c| symmetries(*c)[s]).collect()).collect()
+    }).collect();
+    let weights = tuples.iter().map(|t| vec![0.0; 1 << (4 * t.len())]).collect();
+    Some(NTupleNetwork {tuples, symmetric, weights})
+  }
+
+  pub fn get_tuples(&self) -> &[Vec<u8>] {
+    &self.tuples
+  }
+
+  /// Weights have 16 entries per cell, exponents above 15 share the last one
+  fn index(board: Bitboard, cells: &[u8]) -> usize {
+    cells.iter().fold(0, |index, c| (index << 4) | board.get_cell(*c as usize).min(15) as usize)
+  }
+
+  pub fn value(&self, board: Bitboard) -> f32 {
+    let mut result = 0.0;
+    for (symmetric, weights) in self.symmetric.iter().zip(self.weights.iter()) {
+      for cells in symmetric {
+        result += weights[Self::index(board, cells)];
+      }
+    }
+    result
+  }
+
+  /// Moves the value of the board by `delta`, spread over all its weights
+  pub fn update(&mut self, board: Bitboard, delta: f32) {
+    let step = delta / (self.tuples.len() * 8) as f32;
+    for (symmetric, weights) in self.symmetric.iter().zip(self.weights.iter_mut()) {
+      for cells in symmetric {
+        weights[Self::index(board, cells)] += step;
+      }
+    }
+  }
+
+  /// Move with the biggest score plus value of the board after it, before the spawn
+  fn best_afterstate(&self, board: Bitboard, directions: DirectionSet) -> Option<(Direction, Bitboard, f32)> {
+    directions.iter()
+      .map(|dir| (dir, board.push(dir)))
+      .filter(|(_, (next, _))| *next != board)
+      .map(|(dir, (next, score))| (dir, next, score as f32))
+      .fold(None, |best: Option<(Direction, Bitboard, f32, f32)>, (dir, next, score)| {
+        let value = score + self.value(next);
+        match best {
+          Some((_, _, _, best_value)) if best_value >= value => best,
+          _ => Some((dir, next, score, value)),
+        }
+      })
+      .map(|(dir, next, score, _)| (dir, next, score))
+  }
+
+  /// Writes the magic, the version, the tuples and all weights as little endian `f32`:
+  ///
+  /// `magic: [u8; 8], version: u32, tuple_count: u32,
+  /// tuple_count * (length: u32, cells: [u8; length]),
+  /// for every tuple 16^length weights: f32`
+  pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+    writer.write_all(&NTUPLE_MAGIC)?;
+    writer.write_all(&NTUPLE_VERSION.to_le_bytes())?;
+    writer.write_all(&(self.tuples.len() as u32).to_le_bytes())?;
+    for tuple in &self.tuples {
+      writer.write_all(&(tuple.len() as u32).to_le_bytes())?;
+      writer.write_all(tuple)?;
+    }
+    for weights in &self.weights {
+      for w in weights {
+        writer.write_all(&w.to_le_bytes())?;
+      }
+    }
+    Ok(())
+  }
+
+  pub fn read_from<R: Read>(reader: &mut R) -> io::Result<Self> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+    let read_u32 = |reader: &mut R| -> io::Result<u32> {
+      let mut bytes = [0; 4];
+      reader.read_exact(&mut bytes)?;
+      Ok(u32::from_le_bytes(bytes))
+    };
+
+    let mut magic = [0; 8];
+    reader.read_exact(&mut magic)?;
+    if magic != NTUPLE_MAGIC {
+      return Err(invalid("not an n-tuple weights file"));
+    }
+    let version = read_u32(reader)?;
+    if version != NTUPLE_VERSION {
+      return Err(invalid(&format!("unsupported n-tuple weights version {}", version)));
+    }
+
+    let count = read_u32(reader)? as usize;
+    let mut tuples = Vec::new();
+    for _ in 0 .. count {
+      let length = read_u32(reader)? as usize;
+      if length > MAX_TUPLE_LENGTH {
+        return Err(invalid("n-tuple is too long"));
+      }
+      let mut tuple = vec![0; length];
+      reader.read_exact(&mut tuple)?;
+      tuples.push(tuple);
+    }
+
+    let mut network = NTupleNetwork::new(tuples).ok_or_else(|| invalid("invalid n-tuple"))?;
+    for weights in network.weights.iter_mut() {
+      for w in weights.iter_mut() {
+        let mut bytes = [0; 4];
+        reader.read_exact(&mut bytes)?;
+        *w = f32::from_le_bytes(bytes);
+      }
+    }
+    Ok(network)
+  }
+
+  pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    self.write_to(&mut writer)?;
+    writer.flush()
+  }
+
+  pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+    Self::read_from(&mut BufReader::new(File::open(path)?))
+  }
+}
+
+/// The n-tuple network plays greedily: score plus value of the board after the move.
+/// Fields that don't fit a bitboard have no value, their moves get only the score,
+/// so the game still goes on until no move is left
+impl Agent for NTupleNetwork {
+  fn evaluate(&mut self, field: &Field) -> Vec<(Direction, f64)> {
+    let board = match as_bitboard(field) {
+      Some(board) => board,
+      None => return field.get_rules().directions.iter()
+        .filter_map(|dir| Position::push(field, dir).map(|(_, score)| (dir, score as f64)))
+        .collect(),
+    };
+
+    field.get_rules().directions.iter().filter_map(|dir| {
+      let (next, score) = board.push(dir);
+      if next == board {
+        None
+      } else {
+        Some((dir, score as f64 + self.value(next) as f64))
+      }
+    }).collect()
+  }
+}
+
+/// One finished training game
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TrainedGame {
+  pub score: u64,
+  pub max_number: i32,
+  pub moves: usize,
+}
+
+/// Temporal difference TD(0) self-play on afterstates, the boards right after a move
+/// and before the spawn: the value of every afterstate moves towards the score of the next move
+/// plus the value of the next afterstate, and towards zero when the game is over
+#[derive(Debug, Clone)]
+pub struct Trainer {
+  pub network: NTupleNetwork,
+  /// Step for the value of a board, shared by all its weights
+  pub learning_rate: f32,
+  /// Part of the moves played at random to explore
+  pub exploration: f64,
+  rules: Rules,
+  rng: GameRng,
+}
+
+impl Trainer {
+  /// `None` unless the rules fit a 4x4 bitboard
+  pub fn new(network: NTupleNetwork, rules: &Rules, seed: u64) -> Option<Self> {
+    let field = Field::with_rules(BITBOARD_SIZE, BITBOARD_SIZE, rules.clone());
+    as_bitboard(&field)?;
+    Some(Trainer {
+      network,
+      learning_rate: 0.1,
+      exploration: 0.0,
+      rules: rules.clone(),
+      rng: GameRng::new(seed),
+    })
+  }
+
+  pub fn train_game(&mut self) -> TrainedGame {
+    let mut board = Bitboard(0);
+    for _ in 0 .. 2 {
+      board = spawn_by_rules(&board, &self.rules, None, &mut self.rng).unwrap_or(board);
+    }
+
+    let mut result = TrainedGame {score: 0, max_number: 0, moves: 0};
+    let mut previous: Option<Bitboard> = None;
+    loop {
+      let explore = self.exploration > 0.0
+        && (self.rng.next_u32() as f64) < self.exploration * (u32::MAX as f64 + 1.0);
+      let step = if explore {
+        push_random(&board, self.rules.directions, &mut self.rng).map(|(dir, next, score)| (dir, next, score as f32))
+      } else {
+        self.network.best_afterstate(board, self.rules.directions)
+      };
+
+      let (dir, after, score) = match step {
+        Some(step) => step,
+        None => break,
+      };
+      if let Some(previous) = previous {
+        let target = score + self.network.value(after);
+        let delta = self.learning_rate * (target - self.network.value(previous));
+        self.network.update(previous, delta);
+      }
+
+      result.score += score as u64;
+      result.moves += 1;
+      previous = Some(after);
+      board = spawn_by_rules(&after, &self.rules, Some(dir), &mut self.rng).unwrap_or(after);
+    }
+
+    if let Some(previous) = previous {
+      let delta = -self.learning_rate * self.network.value(previous);
+      self.network.update(previous, delta);
+    }
+    result.max_number = match board.get_max_exponent() {
+      0 => 0,
+      e => 1 << e,
+    };
+    result
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::merge::MergeRules;
+
+  #[test]
+  fn symmetric_boards_have_equal_values() {
+    let mut network = NTupleNetwork::default();
+    let mut rng = GameRng::new(1);
+    for w in network.weights.iter_mut().flatten() {
+      *w = (rng.next_u32() % 1000) as f32;
+    }
+
+    let field = Field::from_numbers(vec![
+      vec![2, 4, 0, 0], vec![0, 8, 0, 0], vec![0, 0, 0, 16], vec![0, 0, 2, 0]
+    ]);
+    let mirrored = Field::from_numbers(field.get_numbers().iter().map(
+      |line| line.iter().rev().copied().collect()
+    ).collect());
+    let board = Bitboard::from_field(&field).unwrap();
+    assert_eq!(network.value(board), network.value(Bitboard::from_field(&mirrored).unwrap()));
+  }
+
+  #[test]
+  fn save_and_load() {
+    let mut trainer = Trainer::new(NTupleNetwork::default(), &Rules::default(), 3).unwrap();
+    for _ in 0 .. 5 {
+      trainer.train_game();
+    }
+
+    let mut bytes = Vec::new();
+    trainer.network.write_to(&mut bytes).unwrap();
+    assert_eq!(&bytes[.. 8], b"2048NTUP");
+    assert_eq!(NTupleNetwork::read_from(&mut bytes.as_slice()).unwrap(), trainer.network);
+
+    bytes[8] = 2;
+    assert!(NTupleNetwork::read_from(&mut bytes.as_slice()).is_err());
+    assert!(NTupleNetwork::read_from(&mut &bytes[.. 100]).is_err());
+  }
+
+  #[test]
+  fn training_improves_play() {
+    let mut trainer = Trainer::new(NTupleNetwork::default(), &Rules::default(), 5).unwrap();
+    let average = |trainer: &mut Trainer, games| {
+      (0 .. games).map(|_| trainer.train_game().score).sum::<u64>() / games
+    };
+    let before = average(&mut trainer, 20);
+    for _ in 0 .. 100 {
+      trainer.train_game();
+    }
+    assert!(average(&mut trainer, 20) > before);
+  }
+
+  #[test]
+  fn only_bitboard_training() {
+    let rules = Rules {merge_rule: MergeRules::Fibonacci, ..Default::default()};
+    assert!(Trainer::new(NTupleNetwork::default(), &rules, 1).is_none());
+    let mut network = NTupleNetwork::default();
+    assert!(network.evaluate(&Field::with_size(5, 5)).is_empty());
+    // Other fields still get their legal moves, scored by the merges
+    let field = Field::from_numbers(vec![vec![2, 2, 0], vec![0, 4, 0], vec![0, 0, 0]]);
+    let moves = network.evaluate(&field);
+    assert_eq!(moves.iter().map(|(d, _)| *d).collect::<DirectionSet>(), field.legal_moves());
+    assert_eq!(network.best_move(&field), Some(Direction::Left));
+    assert!(NTupleNetwork::new(vec![vec![16]]).is_none());
+  }
+}
@@ -0,0 +1,102 @@
+//! Command lines of the binaries: an optional width and height, rule options
+//! and `name=VALUE` options of the binary itself. Anything else is an error
+
+use std::str::FromStr;
+
+use crate::field::DEFAULT_SIZE;
+use crate::rules::Rules;
+
+/// A parsed command line
+#[derive(Debug, Clone)]
+pub struct CommandLine {
+  args: Vec<String>,
+  /// Width and height, a square of `DEFAULT_SIZE` if the command line has none
+  pub size: (usize, usize),
+  /// Rules with the rule options applied, not validated against the size
+  pub rules: Rules,
+}
+
+impl CommandLine {
+  /// Splits `args` into sizes, rule options and `options` of the binary such as `seed=`.
+  /// Sizes are refused unless `sized`, an unknown or malformed argument is an error
+  pub fn parse(args: Vec<String>, options: &[&str], sized: bool) -> Result<Self, String> {
+    let sizes: Vec<usize> = if sized {
+      args.iter().filter_map(|a| a.parse().ok()).collect()
+    } else {
+      Vec::new()
+    };
+    if sizes.len() > 2 || sizes.contains(&0) {
+      return Err("Expected at most a width and a height above zero".to_string());
+    }
+    let width = sizes.first().copied().unwrap_or(DEFAULT_SIZE);
+    let height = sizes.get(1).copied().unwrap_or(width);
+
+    let mut rules = Rules::default();
+    for arg in &args {
+      if (sized && arg.parse::<usize>().is_ok()) || options.iter().any(|o| arg.starts_with(o)) {
+        continue;
+      }
+      match rules.set_option(arg) {
+        Ok(true) => {}
+        Ok(false) => return Err(format!("Unknown argument {}", arg)),
+        Err(e) => return Err(format!("Bad argument {}: {}", arg, e)),
+      }
+    }
+
+    Ok(CommandLine {args, size: (width, height), rules})
+  }
+
+  /// Text after `name`, `None` if the option isn't given
+  pub fn get_option(&self, name: &str) -> Option<&str> {
+    self.args.iter().find_map(|a| a.strip_prefix(name))
+  }
+
+  /// Value of the option `name`, `None` if it isn't given
+  pub fn parse_option<T: FromStr>(&self, name: &str) -> Result<Option<T>, String> {
+    self.get_option(name).map(|value| parse_value(name, value)).transpose()
+  }
+}
+
+/// `value` of the option `name`, the error names both
+pub fn parse_value<T: FromStr>(name: &str, value: &str) -> Result<T, String> {
+  value.parse().map_err(|_| format!("Bad value in {}{}", name, value))
+}
+
+/// Ends a console binary with an error
+pub fn exit_with(message: &str) -> ! {
+  eprintln!("{}", message);
+  std::process::exit(1)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::spawn::SpawnLocation;
+
+  fn parse(args: &[&str], sized: bool) -> Result<CommandLine, String> {
+    CommandLine::parse(args.iter().map(|a| a.to_string()).collect(), &["seed=", "depth="], sized)
+  }
+
+  #[test]
+  fn sizes_rules_and_options() {
+    let command_line = parse(&["5", "fibonacci", "seed=12", "spawn=corners", "3"], true).unwrap();
+    assert_eq!(command_line.size, (5, 3));
+    assert_eq!(command_line.rules.spawn.location, SpawnLocation::CornersFirst);
+    assert_eq!(command_line.parse_option::<u64>("seed="), Ok(Some(12)));
+    assert_eq!(command_line.parse_option::<usize>("depth="), Ok(None));
+
+    let command_line = parse(&["6"], true).unwrap();
+    assert_eq!(command_line.size, (6, 6));
+    assert_eq!(parse(&[], true).unwrap().size, (DEFAULT_SIZE, DEFAULT_SIZE));
+  }
+
+  #[test]
+  fn errors() {
+    assert!(parse(&["4", "4", "4"], true).is_err());
+    assert!(parse(&["0"], true).is_err());
+    assert!(parse(&["4"], false).is_err());
+    assert!(parse(&["sede=1"], true).is_err());
+    assert!(parse(&["target=-8"], true).is_err());
+    assert!(parse(&["seed=x"], true).unwrap().parse_option::<u64>("seed=").is_err());
+  }
+}
@@ -0,0 +1,77 @@
+use std::io::BufRead;
+
+use game::args::{exit_with, parse_value};
+use game::field::Field;
+use game::notation::{write_position, Side};
+use game::replay::{Playback, Replay};
+
+const USAGE: &str = "Usage: replay FILE [to=N] [step]";
+
+fn print_field(playback: &Playback) {
+  let field: &Field = playback.get_game().get_field();
+  let width = field.get_numbers().iter().flatten().max().map_or(1, |n| n.to_string().len());
+  println!(
+    "Move {} of {}, score {}",
+    playback.get_position(), playback.get_replay().moves.len(), field.get_score()
+  );
+  for line in field.get_numbers() {
+    let numbers: Vec<String> = line.iter().map(|n| match n {
+      0 => format!("{:>w$}", ".", w = width),
+      n => format!("{:>w$}", n, w = width),
+    }).collect();
+    println!("{}", numbers.join(" "));
+  }
+  println!("{}", write_position(field, Side::Player));
+  if field.is_game_over() {
+    println!("Game over");
+  }
+}
+
+fn main() {
+  // Command line: replay FILE [to=N] [step]
+  //   to=N shows the field after N moves, the last one by default.
+  //   step reads commands from the standard input: empty line or n - next move,
+  //   b - previous move, a number - go to that move, q - quit
+  let args: Vec<String> = std::env::args().skip(1).collect();
+  let (paths, options): (Vec<&String>, Vec<&String>) =
+    args.iter().partition(|a| !a.contains('=') && *a != "step");
+  if paths.len() != 1 {
+    exit_with(USAGE);
+  }
+  if let Some(option) = options.iter().find(|a| !a.starts_with("to=") && **a != "step") {
+    exit_with(&format!("Unknown argument {}\n{}", option, USAGE));
+  }
+  let to: Option<usize> = args.iter().find_map(|a| a.strip_prefix("to=")).map(
+    |to| parse_value("to=", to).unwrap_or_else(|e| exit_with(&format!("{}\n{}", e, USAGE)))
+  );
+  let path = paths[0];
+  let replay = Replay::load(path).unwrap_or_else(|e| exit_with(&format!("Cannot load {}: {}", path, e)));
+
+  let to = to.unwrap_or(replay.moves.len());
+  let mut playback = Playback::new(replay);
+  playback.seek(to);
+  print_field(&playback);
+  if !args.iter().any(|a| a == "step") {
+    return;
+  }
+
+  for line in std::io::stdin().lock().lines() {
+    let line = match line {
+      Ok(line) => line,
+      Err(_) => break,
+    };
+    match line.trim() {
+      "" | "n" => { playback.step_forward(); },
+      "b" => { playback.step_back(); },
+      "q" => break,
+      command => match command.parse() {
+        Ok(position) => playback.seek(position),
+        Err(_) => {
+          println!("Commands: n - next, b - back, N - go to move N, q - quit");
+          continue;
+        }
+      },
+    }
+    print_field(&playback);
+  }
+}
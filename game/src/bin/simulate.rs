@@ -0,0 +1,166 @@
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+use game::ai::expectimax::Expectimax;
+use game::ai::mcts::{Budget, Mcts};
+use game::ai::ntuple::NTupleNetwork;
+use game::ai::{Agent, RandomAgent};
+use game::args::{exit_with, CommandLine};
+use game::game::Game;
+use game::rules::Rules;
+
+/// What one simulated game ended with
+struct GameResult {
+  score: u64,
+  max_number: i32,
+  moves: usize,
+  won: bool,
+}
+
+/// Options of the simulator itself, everything else must be a size or a rule option
+const OPTIONS: [&str; 8] = ["agent=", "games=", "seed=", "threads=", "depth=", "iterations=", "time=", "network="];
+
+fn play(agent: &mut dyn Agent, width: usize, height: usize, rules: &Rules, seed: u64) -> GameResult {
+  let mut game = Game::new(width, height, rules.clone(), seed);
+  game.set_history_limit(0);
+  let mut moves = 0;
+  let mut won = false;
+  while let Some(dir) = agent.best_move(game.get_field()) {
+    if game.make_move(dir).is_empty() {
+      break;
+    }
+    moves += 1;
+
+    // Play on past the target to see how far the agent gets
+    if game.get_field().is_won() && !game.get_field().is_keep_going() {
+      won = true;
+      game.keep_going();
+    }
+    if game.get_field().is_stopped() {
+      break;
+    }
+  }
+
+  let field = game.get_field();
+  GameResult {
+    score: field.get_score(),
+    max_number: field.get_numbers().iter().flatten().copied().max().unwrap_or(0),
+    moves,
+    won: won || field.is_won(),
+  }
+}
+
+fn percentile(sorted: &[u64], percent: usize) -> u64 {
+  sorted[(sorted.len() - 1) * percent / 100]
+}
+
+fn main() {
+  // Command line: simulate [width [height]] [agent=random|expectimax|mcts|ntuple]
+  //   [games=N] [seed=N] [threads=N] [depth=N] [iterations=N|time=MS] [network=FILE]
+  //   [pow2sum|classic|fibonacci|threes] [all|orthogonal|diagonal] [target=N|target=none]
+  //   [obstacles=X:Y,...|level=corner|diagonal|ring|gates] [wrap]
+  //   [spawn=random|corners|away] [tiles=N] [weights=NUMBER:WEIGHT,...]
+  let command_line = CommandLine::parse(std::env::args().skip(1).collect(), &OPTIONS, true)
+    .unwrap_or_else(|e| exit_with(&e));
+  let option = |name: &str| command_line.get_option(name);
+  let parse = |name: &str| -> Option<usize> {
+    command_line.parse_option(name).unwrap_or_else(|e| exit_with(&e))
+  };
+  let number = |name: &str, default: usize| parse(name).unwrap_or(default);
+  let (width, height) = command_line.size;
+  let rules = &command_line.rules;
+  if let Err(e) = rules.validate(width, height) {
+    exit_with(&format!("Bad rules: {}", e));
+  }
+
+  let agent_name = option("agent=").unwrap_or("expectimax");
+  let games = number("games=", 1000);
+  let first_seed = number("seed=", 0) as u64;
+  let threads = number("threads=", std::thread::available_parallelism().map_or(1, |n| n.get())).max(1);
+  // Search depth of expectimax, moves per rollout of MCTS
+  let depth = parse("depth=");
+  let budget = match parse("time=") {
+    Some(time) => Budget::Time(Duration::from_millis(time as u64)),
+    None => Budget::Iterations(number("iterations=", 200)),
+  };
+  let network = match agent_name {
+    "ntuple" => {
+      let path = option("network=").unwrap_or("ntuple.bin");
+      let network = NTupleNetwork::load(path)
+        .unwrap_or_else(|e| exit_with(&format!("Cannot load {}: {}", path, e)));
+      if game::ai::as_bitboard(&game::field::Field::with_rules(width, height, rules.clone())).is_none() {
+        exit_with("The n-tuple network plays only 4x4 boards of powers of two");
+      }
+      Some(network)
+    }
+    "random" | "expectimax" | "mcts" => None,
+    _ => exit_with(&format!("Unknown agent {}", agent_name)),
+  };
+  let new_agent = |seed: u64| -> Box<dyn Agent> {
+    match agent_name {
+      "random" => Box::new(RandomAgent::new(seed)),
+      "mcts" => {
+        let mut mcts = Mcts::new(budget, seed);
+        mcts.rollout_depth = depth;
+        Box::new(mcts)
+      }
+      "ntuple" => Box::new(network.clone().unwrap()),
+      _ => Box::new(Expectimax::new(depth.unwrap_or(Expectimax::default().depth))),
+    }
+  };
+
+  let start = Instant::now();
+  let mut results: Vec<GameResult> = std::thread::scope(|scope| {
+    let workers: Vec<_> = (0 .. threads).map(|thread| {
+      let new_agent = &new_agent;
+      scope.spawn(move || {
+        let mut agent = new_agent(first_seed + thread as u64);
+        (thread .. games).step_by(threads)
+          .map(|game| play(agent.as_mut(), width, height, rules, first_seed + game as u64))
+          .collect::<Vec<_>>()
+      })
+    }).collect();
+    workers.into_iter().flat_map(|w| w.join().unwrap()).collect()
+  });
+  let elapsed = start.elapsed().as_secs_f64();
+  if results.is_empty() {
+    exit_with("No games played");
+  }
+
+  let count = results.len();
+  println!(
+    "{} games of {}x{} {} {} with {}, seeds {} .. {}",
+    count, width, height, rules.merge_rule.get_name(),
+    rules.directions.get_name().unwrap_or("custom"), agent_name,
+    first_seed, first_seed + count as u64 - 1
+  );
+  println!("{:.1} s, {:.2} games/s", elapsed, count as f64 / elapsed);
+  println!(
+    "Average moves {:.1}, won {:.1}%",
+    results.iter().map(|r| r.moves).sum::<usize>() as f64 / count as f64,
+    100.0 * results.iter().filter(|r| r.won).count() as f64 / count as f64
+  );
+
+  let mut max_numbers = BTreeMap::new();
+  for result in &results {
+    *max_numbers.entry(result.max_number).or_insert(0) += 1;
+  }
+  println!("Max tile       games   reached");
+  let mut reached = count;
+  for (number, games) in &max_numbers {
+    println!(
+      "{:8} {:8} {:6.1}% {:6.1}%",
+      number, games, 100.0 * *games as f64 / count as f64, 100.0 * reached as f64 / count as f64
+    );
+    reached -= games;
+  }
+
+  results.sort_by_key(|r| r.score);
+  let scores: Vec<u64> = results.iter().map(|r| r.score).collect();
+  println!(
+    "Score: average {}, min {}, 10% {}, 25% {}, median {}, 75% {}, 90% {}, max {}",
+    scores.iter().sum::<u64>() / count as u64, scores[0],
+    percentile(&scores, 10), percentile(&scores, 25), percentile(&scores, 50),
+    percentile(&scores, 75), percentile(&scores, 90), scores[count - 1]
+  );
+}
@@ -0,0 +1,66 @@
+use std::str::FromStr;
+use std::time::Instant;
+
+use game::ai::ntuple::{NTupleNetwork, Trainer};
+use game::args::{exit_with, CommandLine};
+
+/// Options of the trainer itself, everything else must be a rule option
+const OPTIONS: [&str; 6] = ["games=", "rate=", "explore=", "seed=", "load=", "out="];
+
+fn parse<T: FromStr>(command_line: &CommandLine, name: &str) -> Option<T> {
+  command_line.parse_option(name).unwrap_or_else(|e| exit_with(&e))
+}
+
+fn main() {
+  // Command line: train [games=N] [rate=F] [explore=F] [seed=N] [load=FILE] [out=FILE]
+  //   [pow2sum|classic] [all|orthogonal|diagonal]
+  let command_line = CommandLine::parse(std::env::args().skip(1).collect(), &OPTIONS, false)
+    .unwrap_or_else(|e| exit_with(&e));
+  let option = |name: &str| command_line.get_option(name);
+  let games = parse(&command_line, "games=").unwrap_or(10000);
+  let seed = parse(&command_line, "seed=").unwrap_or(1);
+  let out = option("out=").unwrap_or("ntuple.bin");
+  let rules = &command_line.rules;
+
+  let network = match option("load=") {
+    Some(path) => NTupleNetwork::load(path)
+      .unwrap_or_else(|e| exit_with(&format!("Cannot load {}: {}", path, e))),
+    None => NTupleNetwork::default(),
+  };
+  let mut trainer = Trainer::new(network, rules, seed)
+    .unwrap_or_else(|| exit_with("The n-tuple network plays only 4x4 boards of powers of two"));
+  if let Some(rate) = parse(&command_line, "rate=") {
+    trainer.learning_rate = rate;
+  }
+  if let Some(exploration) = parse(&command_line, "explore=") {
+    trainer.exploration = exploration;
+  }
+
+  let report_every = 1000;
+  let start = Instant::now();
+  let (mut score, mut max_number, mut reached_target) = (0, 0, 0);
+  for game in 1 ..= games {
+    let result = trainer.train_game();
+    score += result.score;
+    max_number = max_number.max(result.max_number);
+    if rules.target.is_some_and(|t| result.max_number >= t) {
+      reached_target += 1;
+    }
+
+    if game % report_every == 0 || game == games {
+      let count = (game - 1) % report_every + 1;
+      println!(
+        "{} games, {:.1} s: average score {}, best tile {}, target reached {:.1}%",
+        game, start.elapsed().as_secs_f64(), score / count as u64, max_number,
+        100.0 * reached_target as f64 / count as f64
+      );
+      score = 0;
+      max_number = 0;
+      reached_target = 0;
+    }
+  }
+
+  if let Err(e) = trainer.network.save(out) {
+    exit_with(&format!("Cannot save {}: {}", out, e));
+  }
+}
@@ -0,0 +1,296 @@
+use std::sync::OnceLock;
+
+use crate::direction::{Direction, DirectionSet};
+use crate::field::{Field, Numbers};
+use crate::merge::MergeRules;
+
+pub const BITBOARD_SIZE: usize = 4;
+/// Biggest exponent a cell can hold, the biggest power of two in an `i32`. Two such tiles don't merge
+pub const MAX_EXPONENT: u8 = 30;
+/// Bits of a cell on the board and of an exponent in a packed line of the tables
+const CELL_BITS: usize = 8;
+const LINE_BITS: usize = 5;
+const LINE_MASK: u32 = (1 << LINE_BITS) - 1;
+
+const CELLS: usize = BITBOARD_SIZE * BITBOARD_SIZE;
+const MAX_LINES: usize = BITBOARD_SIZE * 2 - 1;
+
+/// 4x4 field packed into a `u128`: cell (x, y) keeps the exponent of its number
+/// in bits `8 * (y * 4 + x) ..`, zero for an empty cell.
+/// Moves go through precomputed line tables and give the same result as `Field::push_dir`
+/// with the power-of-two-sum or the equal tiles rule for every field whose numbers fit an `i32`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Bitboard(pub u128);
+
+struct Tables {
+  /// Cells of every line for every direction, starting from the one tiles are pushed to
+  lines: [[[u8; BITBOARD_SIZE]; MAX_LINES]; 8],
+  line_lengths: [[u8; MAX_LINES]; 8],
+  line_counts: [usize; 8],
+  /// Packed line of four exponents, head in the low bits, to the line after the push
+  moves: Box<[u32]>,
+  scores: Box<[u32]>,
+}
+
+fn tables() -> &'static Tables {
+  static TABLES: OnceLock<Tables> = OnceLock::new();
+  TABLES.get_or_init(Tables::new)
+}
+
+impl Tables {
+  fn new() -> Self {
+    let mut tables = Tables {
+      lines: [[[0; BITBOARD_SIZE]; MAX_LINES]; 8],
+      line_lengths: [[0; MAX_LINES]; 8],
+      line_counts: [0; 8],
+      moves: vec![0; 1 << (LINE_BITS * BITBOARD_SIZE)].into_boxed_slice(),
+      scores: vec![0; 1 << (LINE_BITS * BITBOARD_SIZE)].into_boxed_slice(),
+    };
+
+    let valid = |x: i32, y: i32| x >= 0 && x < BITBOARD_SIZE as i32 && y >= 0 && y < BITBOARD_SIZE as i32;
+    for dir in Direction::ALL.iter() {
+      let d = dir.get_index();
+      let (dx, dy) = dir.get_delta();
+      for y in 0 .. BITBOARD_SIZE as i32 {
+        for x in 0 .. BITBOARD_SIZE as i32 {
+          if valid(x + dx, y + dy) {
+            continue;
+          }
+
+          let line = tables.line_counts[d];
+          tables.line_counts[d] += 1;
+          let (mut cur_x, mut cur_y) = (x, y);
+          while valid(cur_x, cur_y) {
+            let k = tables.line_lengths[d][line] as usize;
+            tables.lines[d][line][k] = (cur_y * BITBOARD_SIZE as i32 + cur_x) as u8;
+            tables.line_lengths[d][line] += 1;
+            cur_x -= dx;
+            cur_y -= dy;
+          }
+        }
+      }
+    }
+
+    for packed in 0 .. 1 << (LINE_BITS * BITBOARD_SIZE) {
+      let (result, score) = Self::push_line(packed as u32);
+      tables.moves[packed] = result;
+      tables.scores[packed] = score;
+    }
+
+    tables
+  }
+
+  /// The same sliding as `Field::push_dir`, on exponents
+  fn push_line(packed: u32) -> (u32, u32) {
+    let mut line = [0u8; BITBOARD_SIZE];
+    for (k, e) in line.iter_mut().enumerate() {
+      *e = ((packed >> (LINE_BITS * k)) & LINE_MASK) as u8;
+    }
+
+    let mut score = 0;
+    let mut i = 0;
+    let mut j = 1;
+    while i < line.len() {
+      while j == i || (j < line.len() && line[j] == 0) {
+        j += 1;
+      }
+
+      if j == line.len() {
+        break;
+      }
+
+      if line[i] == 0 {
+        line[i] = line[j];
+        line[j] = 0;
+        continue;
+      }
+
+      // Exponents above the maximum are never on a board, their lines are left as they are
+      if line[i] == line[j] && line[i] < MAX_EXPONENT {
+        line[i] += 1;
+        line[j] = 0;
+        score += 1 << line[i];
+      }
+
+      i += 1;
+    }
+
+    let mut result = 0;
+    for (k, e) in line.iter().enumerate() {
+      result |= (*e as u32) << (LINE_BITS * k);
+    }
+    (result, score)
+  }
+}
+
+impl Bitboard {
+  /// `None` unless the field is 4x4, plays by a rule the bitboard implements
+  /// and holds only powers of two
+  pub fn from_field(field: &Field) -> Option<Self> {
+    let rule = field.get_rules().merge_rule;
+    if field.get_size() != (BITBOARD_SIZE, BITBOARD_SIZE)
+    || !field.get_rules().obstacles.is_empty()
+    || field.get_rules().wrap
+    || (rule != MergeRules::PowerOfTwoSum && rule != MergeRules::EqualTiles) {
+      return None;
+    }
+
+    let mut result = Bitboard(0);
+    for (y, line) in field.get_numbers().iter().enumerate() {
+      for (x, n) in line.iter().enumerate() {
+        let e = match *n {
+          0 => 0,
+          n if n >= 2 && n & (n - 1) == 0 && n.trailing_zeros() <= MAX_EXPONENT as u32 => {
+            n.trailing_zeros() as u8
+          }
+          _ => return None,
+        };
+        result.set_exponent(x, y, e);
+      }
+    }
+
+    Some(result)
+  }
+
+  pub fn get_numbers(&self) -> Numbers {
+    (0 .. BITBOARD_SIZE).map(
+      |y| (0 .. BITBOARD_SIZE).map(|x| self.get_number(x, y)).collect()
+    ).collect()
+  }
+
+  pub fn get_exponent(&self, x: usize, y: usize) -> u8 {
+    self.get_cell(y * BITBOARD_SIZE + x)
+  }
+
+  /// Exponent of the cell `y * 4 + x`
+  pub(crate) fn get_cell(&self, cell: usize) -> u8 {
+    (self.0 >> (CELL_BITS * cell)) as u8
+  }
+
+  pub fn set_exponent(&mut self, x: usize, y: usize, exponent: u8) {
+    debug_assert!(exponent <= MAX_EXPONENT);
+    let shift = CELL_BITS * (y * BITBOARD_SIZE + x);
+    self.0 = (self.0 & !(0xFF << shift)) | ((exponent as u128) << shift);
+  }
+
+  pub fn get_number(&self, x: usize, y: usize) -> i32 {
+    match self.get_exponent(x, y) {
+      0 => 0,
+      e => 1 << e,
+    }
+  }
+
+  pub fn get_max_exponent(&self) -> u8 {
+    (0 .. CELLS).map(|c| self.get_cell(c)).max().unwrap_or(0)
+  }
+
+  /// Bit `y * 4 + x` is set for every empty cell
+  pub fn get_empty_mask(&self) -> u16 {
+    let mut result = 0;
+    for c in 0 .. CELLS {
+      if self.get_cell(c) == 0 {
+        result |= 1 << c;
+      }
+    }
+    result
+  }
+
+  pub fn count_empty(&self) -> u32 {
+    self.get_empty_mask().count_ones()
+  }
+
+  /// The field after pushing to `dir` and the score for it, the same board if nothing moved
+  pub fn push(&self, dir: Direction) -> (Bitboard, u64) {
+    let tables = tables();
+    let d = dir.get_index();
+    let mut board = self.0;
+    let mut score = 0;
+    for line in 0 .. tables.line_counts[d] {
+      let cells = &tables.lines[d][line][.. tables.line_lengths[d][line] as usize];
+      let mut packed = 0u32;
+      for (k, c) in cells.iter().enumerate() {
+        packed |= (Bitboard(board).get_cell(*c as usize) as u32) << (LINE_BITS * k);
+      }
+
+      let result = tables.moves[packed as usize];
+      if result == packed {
+        continue;
+      }
+
+      score += tables.scores[packed as usize] as u64;
+      for (k, c) in cells.iter().enumerate() {
+        let e = ((result >> (LINE_BITS * k)) & LINE_MASK) as u128;
+        let shift = CELL_BITS * *c as usize;
+        board = (board & !(0xFF << shift)) | (e << shift);
+      }
+    }
+
+    (Bitboard(board), score)
+  }
+
+  /// Directions from `directions` that change the field
+  pub fn legal_moves(&self, directions: DirectionSet) -> DirectionSet {
+    directions.iter().filter(|dir| self.push(*dir).0 != *self).collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::rng::{GameRng, gen_below};
+
+  fn random_field(rng: &mut GameRng) -> Field {
+    Field::from_numbers((0 .. 4).map(
+      |_| (0 .. 4).map(|_| [0, 0, 2, 4, 8, 16, 2048, 16384][gen_below(rng, 8)]).collect()
+    ).collect())
+  }
+
+  #[test]
+  fn round_trip() {
+    let mut rng = GameRng::new(3);
+    let field = random_field(&mut rng);
+    let board = Bitboard::from_field(&field).unwrap();
+    assert_eq!(&board.get_numbers(), field.get_numbers());
+  }
+
+  #[test]
+  fn unsupported_fields() {
+    assert_eq!(Bitboard::from_field(&Field::with_size(5, 4)), None);
+    assert_eq!(Bitboard::from_field(&Field::from_numbers(vec![vec![3; 4]; 4])), None);
+    let rules = crate::rules::Rules {obstacles: vec![(0, 0)], ..Default::default()};
+    assert_eq!(Bitboard::from_field(&Field::with_rules(4, 4, rules)), None);
+    let rules = crate::rules::Rules {wrap: true, ..Default::default()};
+    assert_eq!(Bitboard::from_field(&Field::with_rules(4, 4, rules)), None);
+  }
+
+  #[test]
+  fn same_as_field() {
+    let mut rng = GameRng::new(5);
+    for _ in 0 .. 2000 {
+      let field = random_field(&mut rng);
+      let board = Bitboard::from_field(&field).unwrap();
+      assert_eq!(board.legal_moves(DirectionSet::ALL), field.legal_moves());
+      for dir in Direction::ALL.iter() {
+        let mut field = field.clone();
+        let (pushed, score) = board.push(*dir);
+        field.push_dir(*dir);
+        assert_eq!(&pushed.get_numbers(), field.get_numbers());
+        assert_eq!(score, field.get_score());
+      }
+    }
+  }
+
+  #[test]
+  fn big_tiles_merge() {
+    let field = Field::from_numbers(vec![
+      vec![32768, 32768, 0, 0], vec![65536, 65536, 1 << 20, 1 << 20], vec![0; 4], vec![1 << 30, 0, 0, 1 << 30]
+    ]);
+    let board = Bitboard::from_field(&field).unwrap();
+    assert_eq!(&board.get_numbers(), field.get_numbers());
+    let (pushed, score) = board.push(Direction::Left);
+    assert_eq!(pushed.get_numbers(), vec![
+      vec![65536, 0, 0, 0], vec![131072, 1 << 21, 0, 0], vec![0; 4], vec![1 << 30, 1 << 30, 0, 0]
+    ]);
+    assert_eq!(score, 65536 + 131072 + (1 << 21));
+  }
+}
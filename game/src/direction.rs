@@ -0,0 +1,162 @@
+/// One of the eight directions tiles can be pushed to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+  UpLeft,
+  Up,
+  UpRight,
+  Left,
+  Right,
+  DownLeft,
+  Down,
+  DownRight,
+}
+
+impl Direction {
+  /// All directions in the numpad order: 7 8 9 4 6 1 2 3
+  pub const ALL: [Direction; 8] = [
+    Direction::UpLeft, Direction::Up, Direction::UpRight,
+    Direction::Left, Direction::Right,
+    Direction::DownLeft, Direction::Down, Direction::DownRight,
+  ];
+
+  /// Returns (dx, dy), y grows downwards
+  pub fn get_delta(self) -> (i32, i32) {
+    match self {
+      Direction::UpLeft => (-1, -1),
+      Direction::Up => (0, -1),
+      Direction::UpRight => (1, -1),
+      Direction::Left => (-1, 0),
+      Direction::Right => (1, 0),
+      Direction::DownLeft => (-1, 1),
+      Direction::Down => (0, 1),
+      Direction::DownRight => (1, 1),
+    }
+  }
+
+  pub fn from_delta(dx: i32, dy: i32) -> Option<Self> {
+    Self::ALL.iter().copied().find(|d| d.get_delta() == (dx, dy))
+  }
+
+  pub fn is_diagonal(self) -> bool {
+    let (dx, dy) = self.get_delta();
+    dx != 0 && dy != 0
+  }
+
+  pub fn opposite(self) -> Self {
+    let (dx, dy) = self.get_delta();
+    Self::from_delta(-dx, -dy).unwrap()
+  }
+
+  /// Position in `ALL`
+  pub fn get_index(self) -> usize {
+    self as usize
+  }
+
+  /// Digit of the numpad key for the direction, '8' is up
+  pub fn get_digit(self) -> char {
+    let (dx, dy) = self.get_delta();
+    (b'5' as i32 + dx - 3 * dy) as u8 as char
+  }
+
+  pub fn from_digit(digit: char) -> Option<Self> {
+    Self::ALL.iter().copied().find(|d| d.get_digit() == digit)
+  }
+}
+
+/// Directions allowed in a game
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DirectionSet(u8);
+
+impl Default for DirectionSet {
+  fn default() -> Self {
+    DirectionSet::ALL
+  }
+}
+
+impl DirectionSet {
+  pub const EMPTY: DirectionSet = DirectionSet(0);
+  pub const ORTHOGONAL: DirectionSet = DirectionSet(0b01011010);
+  pub const DIAGONAL: DirectionSet = DirectionSet(0b10100101);
+  pub const ALL: DirectionSet = DirectionSet(0b11111111);
+
+  const NAMED: [(DirectionSet, &'static str); 3] = [
+    (DirectionSet::ORTHOGONAL, "orthogonal"),
+    (DirectionSet::DIAGONAL, "diagonal"),
+    (DirectionSet::ALL, "all"),
+  ];
+
+  pub fn contains(self, direction: Direction) -> bool {
+    self.0 & (1 << direction.get_index()) != 0
+  }
+
+  pub fn insert(&mut self, direction: Direction) {
+    self.0 |= 1 << direction.get_index();
+  }
+
+  pub fn remove(&mut self, direction: Direction) {
+    self.0 &= !(1 << direction.get_index());
+  }
+
+  pub fn is_empty(self) -> bool {
+    self.0 == 0
+  }
+
+  pub fn len(self) -> usize {
+    self.0.count_ones() as usize
+  }
+
+  pub fn iter(self) -> impl Iterator<Item = Direction> {
+    Direction::ALL.iter().copied().filter(move |d| self.contains(*d))
+  }
+
+  /// Name of one of the predefined sets
+  pub fn get_name(self) -> Option<&'static str> {
+    Self::NAMED.iter().find(|(set, _)| *set == self).map(|(_, name)| *name)
+  }
+
+  pub fn from_name(name: &str) -> Option<Self> {
+    Self::NAMED.iter().find(|(_, n)| *n == name).map(|(set, _)| *set)
+  }
+}
+
+impl std::iter::FromIterator<Direction> for DirectionSet {
+  fn from_iter<I: IntoIterator<Item = Direction>>(iter: I) -> Self {
+    let mut result = DirectionSet::EMPTY;
+    for direction in iter {
+      result.insert(direction);
+    }
+    result
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn predefined_sets() {
+    assert_eq!(DirectionSet::ORTHOGONAL.len(), 4);
+    assert!(DirectionSet::ORTHOGONAL.iter().all(|d| !d.is_diagonal()));
+    assert_eq!(DirectionSet::DIAGONAL.len(), 4);
+    assert!(DirectionSet::DIAGONAL.iter().all(|d| d.is_diagonal()));
+    assert_eq!(DirectionSet::ALL.iter().collect::<DirectionSet>(), DirectionSet::ALL);
+  }
+
+  #[test]
+  fn opposite() {
+    for d in Direction::ALL.iter() {
+      assert_eq!(d.opposite().opposite(), *d);
+      assert_ne!(d.opposite(), *d);
+    }
+  }
+
+  #[test]
+  fn digits() {
+    let digits: String = Direction::ALL.iter().map(|d| d.get_digit()).collect();
+    assert_eq!(digits, "78946123");
+    for d in Direction::ALL.iter() {
+      assert_eq!(Direction::from_digit(d.get_digit()), Some(*d));
+    }
+    assert_eq!(Direction::from_digit('5'), None);
+  }
+}
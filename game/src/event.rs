@@ -0,0 +1,23 @@
+/// Cell coordinates as (x, y)
+pub type Cell = (usize, usize);
+
+/// What happened to the tiles during a move, enough to animate or replay it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoveEvent {
+  /// A tile slid without merging
+  Moved {from: Cell, to: Cell, number: i32},
+  /// Two tiles joined, `from[0]` is the one that was closer to the edge
+  Merged {from: [Cell; 2], numbers: [i32; 2], to: Cell, number: i32},
+  /// A new tile appeared
+  Spawned {at: Cell, number: i32},
+}
+
+impl MoveEvent {
+  /// Points the event adds to the score
+  pub fn get_score(&self) -> u64 {
+    match self {
+      MoveEvent::Merged {number, ..} => *number as u64,
+      _ => 0,
+    }
+  }
+}
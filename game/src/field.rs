@@ -0,0 +1,655 @@
+use rand::RngCore;
+use smallvec::SmallVec;
+use std::cmp::max;
+
+use crate::direction::{Direction, DirectionSet};
+use crate::event::MoveEvent;
+use crate::merge::MergeRule;
+use crate::rules::Rules;
+use crate::spawn::SpawnPolicy;
+
+pub const DEFAULT_SIZE : usize = 4;
+
+// Inline capacities for the temporary buffers, bigger boards just spill to the heap
+const LINE_CAPACITY : usize = 8;
+const LINES_CAPACITY : usize = LINE_CAPACITY * 2;
+
+pub type Numbers = Vec<Vec<i32>>;
+
+/// Cells as (y, x), starting from the one tiles are pushed to
+type Line = SmallVec<[(usize, usize); LINE_CAPACITY]>;
+type Lines = SmallVec<[Line; LINES_CAPACITY]>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Field {
+  numbers: Numbers,
+  rules: Rules,
+  pub(crate) score: u64,
+  pub(crate) best_score: u64,
+  pub(crate) won: bool,
+  pub(crate) keep_going: bool,
+  pub(crate) game_over: bool,
+}
+
+impl Default for Field {
+  fn default() -> Self {
+    Field::with_size(DEFAULT_SIZE, DEFAULT_SIZE)
+  }
+}
+
+impl Field {
+  pub fn new() -> Self {
+    Field::default()
+  }
+
+  pub fn with_size(width: usize, height: usize) -> Self {
+    Field::with_rules(width, height, Rules::default())
+  }
+
+  pub fn with_rules(width: usize, height: usize, rules: Rules) -> Self {
+    assert!(width > 0 && height > 0);
+    Field {
+      numbers: vec![vec![0; width]; height],
+      rules,
+      score: 0,
+      best_score: 0,
+      won: false,
+      keep_going: false,
+      game_over: false,
+    }
+  }
+
+  pub fn from_numbers(numbers: Numbers) -> Self {
+    assert!(!numbers.is_empty() && !numbers[0].is_empty());
+    assert!(numbers.iter().all(|line| line.len() == numbers[0].len()));
+    Field {numbers, ..Field::with_size(1, 1)}
+  }
+
+  pub fn get_rules(&self) -> &Rules {
+    &self.rules
+  }
+
+  pub fn set_rules(&mut self, rules: Rules) {
+    self.rules = rules;
+  }
+
+  /// Sets the rules if they fit the size of the field and no tile stands on an obstacle
+  pub fn try_set_rules(&mut self, rules: Rules) -> Result<(), String> {
+    let (width, height) = self.get_size();
+    rules.validate(width, height)?;
+    if let Some((x, y)) = rules.obstacles.iter().find(|(x, y)| self.numbers[*y][*x] != 0) {
+      return Err(format!("a tile on obstacle {}:{}", x, y));
+    }
+    self.rules = rules;
+    Ok(())
+  }
+
+  /// Returns (width, height), the same order as `ImageSize`
+  pub fn get_size(&self) -> (usize, usize) {
+    (self.numbers[0].len(), self.numbers.len())
+  }
+
+  pub fn get_numbers(&self) -> &Numbers {
+    &self.numbers
+  }
+
+  pub fn get_numbers_mut(&mut self) -> &mut [Vec<i32>] {
+    &mut self.numbers
+  }
+
+  /// Empty cells as (x, y), row by row, obstacles are not empty
+  pub fn get_empty_cells(&self) -> Vec<(usize, usize)> {
+    let mut result = Vec::new();
+    for (y, line) in self.numbers.iter().enumerate() {
+      for (x, n) in line.iter().enumerate() {
+        if *n == 0 && !self.rules.is_obstacle((x, y)) {
+          result.push((x, y));
+        }
+      }
+    }
+    result
+  }
+
+  /// Sum of all merged numbers in the current game
+  pub fn get_score(&self) -> u64 {
+    self.score
+  }
+
+  /// Best score since the field was created, kept over `new_game`
+  pub fn get_best_score(&self) -> u64 {
+    self.best_score
+  }
+
+  pub(crate) fn raise_best_score(&mut self, best_score: u64) {
+    self.best_score = max(self.best_score, best_score);
+  }
+
+  pub fn is_game_over(&self) -> bool {
+    self.game_over
+  }
+
+  /// True once a tile reached the target of the rules
+  pub fn is_won(&self) -> bool {
+    self.won
+  }
+
+  pub fn is_keep_going(&self) -> bool {
+    self.keep_going
+  }
+
+  /// Lets the player continue after the win
+  pub fn keep_going(&mut self) {
+    self.keep_going = true;
+  }
+
+  /// No more moves until a new game, or until `keep_going` after a win
+  pub fn is_stopped(&self) -> bool {
+    self.game_over || (self.won && !self.keep_going)
+  }
+
+  pub fn new_game(&mut self, rng: &mut impl RngCore) {
+    let (width, height) = self.get_size();
+    let best_score = self.best_score;
+    *self = Self::with_rules(width, height, self.rules.clone());
+    self.best_score = best_score;
+    self.add_item(None, rng);
+    self.add_item(None, rng);
+  }
+
+  /// No direction of the rules would change the field
+  pub fn fail(&self) -> bool {
+    self.legal_moves().is_empty()
+  }
+
+  /// Adds new tiles after `last_move`, `None` for the start of a game
+  pub fn add_item(&mut self, last_move: Option<Direction>, rng: &mut impl RngCore) -> Vec<MoveEvent> {
+    let spawn = self.rules.spawn.clone();
+    self.add_item_with(last_move, &spawn, rng)
+  }
+
+  /// Same as `add_item`, but new tiles are chosen by `policy` instead of the game rules
+  pub fn add_item_with<P: SpawnPolicy>(
+    &mut self,
+    last_move: Option<Direction>,
+    policy: &P,
+    rng: &mut impl RngCore
+  ) -> Vec<MoveEvent> {
+    let items = policy.spawn(self, last_move, rng);
+    if items.is_empty() {
+      self.game_over = true;
+      return Vec::new();
+    }
+
+    let mut events = Vec::with_capacity(items.len());
+    for (x, y, n) in items {
+      debug_assert!(self.numbers[y][x] == 0);
+      self.numbers[y][x] = n;
+      events.push(MoveEvent::Spawned {at: (x, y), number: n});
+    }
+
+    if self.fail() {
+      self.game_over = true;
+    }
+
+    events
+  }
+
+  fn get_lines(&self, dir: Direction) -> Lines {
+    if self.rules.wrap {
+      return self.get_wrapped_lines(dir);
+    }
+
+    let (dx, dy) = dir.get_delta();
+    let mut lines = Lines::new();
+
+    let (width, height) = self.get_size();
+    let valid = |x: i32, y: i32| -> bool {
+      x >= 0 && x < width as i32 && y >= 0 && y < height as i32
+    };
+
+    for y in 0 .. self.numbers.len() as i32 {
+      for x in 0 .. self.numbers[y as usize].len() as i32 {
+        if !valid(x + dx, y + dy) {
+          let mut line = Line::new();
+          let mut cur_x = x;
+          let mut cur_y = y;
+          while valid(cur_x, cur_y) {
+            if self.rules.is_obstacle((cur_x as usize, cur_y as usize)) {
+              // Tiles behind an obstacle are pushed towards it, as towards an edge
+              if !line.is_empty() {
+                lines.push(std::mem::take(&mut line));
+              }
+            } else {
+              line.push((cur_y as usize, cur_x as usize));
+            }
+            cur_x -= dx;
+            cur_y -= dy;
+          }
+          if !line.is_empty() {
+            lines.push(line);
+          }
+        }
+      }
+    }
+
+    lines
+  }
+
+  /// Lines of a board without edges, see `push_dir` for how the rings are cut
+  fn get_wrapped_lines(&self, dir: Direction) -> Lines {
+    let (dx, dy) = dir.get_delta();
+    let (width, height) = self.get_size();
+    let ahead = |x: usize, y: usize| -> (usize, usize) {
+      (
+        (x as i32 + dx).rem_euclid(width as i32) as usize,
+        (y as i32 + dy).rem_euclid(height as i32) as usize
+      )
+    };
+
+    let mut lines = Lines::new();
+    let mut seen = vec![vec![false; width]; height];
+    for y in 0 .. height {
+      for x in 0 .. width {
+        let (next_x, next_y) = (x as i32 + dx, y as i32 + dy);
+        let at_edge = next_x < 0 || next_x >= width as i32 || next_y < 0 || next_y >= height as i32;
+        if seen[y][x] || !at_edge {
+          continue;
+        }
+
+        // Cells of the ring in the order of the move, from this edge cell
+        let mut ring = Vec::new();
+        let mut cell = (x, y);
+        loop {
+          seen[cell.1][cell.0] = true;
+          ring.push(cell);
+          cell = ahead(cell.0, cell.1);
+          if cell == (x, y) {
+            break;
+          }
+        }
+
+        // The ring read backwards from the end of its line, or from an obstacle
+        let end = ring.iter().position(|c| self.rules.is_obstacle(*c))
+          .or_else(|| ring.iter().position(|(x, y)| self.numbers[*y][*x] == 0))
+          .unwrap_or(0);
+        ring.rotate_left(end + 1);
+
+        let mut line = Line::new();
+        for (x, y) in ring.iter().rev() {
+          if self.rules.is_obstacle((*x, *y)) {
+            if !line.is_empty() {
+              lines.push(std::mem::take(&mut line));
+            }
+          } else {
+            line.push((*y, *x));
+          }
+        }
+        if !line.is_empty() {
+          lines.push(line);
+        }
+      }
+    }
+
+    lines
+  }
+
+  /// Whether pushing to `dir` would change anything: a tile has an empty cell before it
+  /// or merges with the next tile of its line
+  pub fn can_push(&self, dir: Direction) -> bool {
+    if !self.rules.directions.contains(dir) {
+      return false;
+    }
+
+    let rule = &self.rules.merge_rule;
+    for l in &self.get_lines(dir) {
+      let mut seen_empty = false;
+      let mut previous = 0;
+      for (y, x) in l {
+        let n = self.numbers[*y][*x];
+        if n == 0 {
+          seen_empty = true;
+          continue;
+        }
+
+        if seen_empty || (previous != 0 && rule.merge(previous, n).is_some()) {
+          return true;
+        }
+        previous = n;
+      }
+    }
+
+    false
+  }
+
+  /// Directions of the rules that would change the field
+  pub fn legal_moves(&self) -> DirectionSet {
+    self.rules.directions.iter().filter(|dir| self.can_push(*dir)).collect()
+  }
+
+  /// Returns what moved and merged, nothing if `dir` is not allowed by the rules.
+  ///
+  /// With `Rules::wrap` moving by `dir` from any cell goes around the board and comes back,
+  /// so the cells form rings. A ring is started at the first cell in row order that has
+  /// an edge ahead, and its line ends at the first empty cell from there on: the tiles at
+  /// the edge go over it. A full ring ends at that edge cell. Rings with obstacles are split
+  /// at them into lines that end before each obstacle
+  pub fn push_dir(&mut self, dir: Direction) -> Vec<MoveEvent> {
+    let rule = self.rules.merge_rule;
+    self.push_dir_with(dir, &rule)
+  }
+
+  /// Same as `push_dir`, but merges tiles by `rule` instead of the one from the game rules
+  pub fn push_dir_with<R: MergeRule + ?Sized>(&mut self, dir: Direction, rule: &R) -> Vec<MoveEvent> {
+    if !self.rules.directions.contains(dir) {
+      return Vec::new();
+    }
+
+    let lines = self.get_lines(dir);
+    let mut result = Vec::new();
+
+    for l in &lines {
+      // Numbers before the move and the original positions of the tiles now at each place
+      let before: SmallVec<[i32; LINE_CAPACITY]> =
+        l.iter().map(|(y, x)| self.numbers[*y][*x]).collect();
+      let mut origins: SmallVec<[(Option<usize>, Option<usize>); LINE_CAPACITY]> =
+        (0 .. l.len()).map(|k| (if before[k] != 0 {Some(k)} else {None}, None)).collect();
+
+      let mut i = 0;
+      let mut j = 1;
+      while i < l.len() {
+        while j == i || (j < l.len() && self.numbers[l[j].0][l[j].1] == 0) {
+          j += 1;
+        }
+
+        if j == l.len() {
+          break;
+        }
+
+        if self.numbers[l[i].0][l[i].1] == 0 {
+          self.numbers[l[i].0][l[i].1] = self.numbers[l[j].0][l[j].1];
+          self.numbers[l[j].0][l[j].1] = 0;
+          origins[i] = origins[j];
+          origins[j] = (None, None);
+          continue;
+        }
+
+        let possible_number =
+          rule.merge(self.numbers[l[i].0][l[i].1], self.numbers[l[j].0][l[j].1]);
+
+        if let Some(possible_number) = possible_number {
+          self.numbers[l[i].0][l[i].1] = possible_number;
+          self.numbers[l[j].0][l[j].1] = 0;
+          origins[i].1 = origins[j].0;
+          origins[j] = (None, None);
+          self.score += possible_number as u64;
+          if self.rules.target.is_some_and(|t| possible_number >= t) {
+            self.won = true;
+          }
+        }
+
+        i += 1;
+      }
+
+      let cell = |k: usize| (l[k].1, l[k].0);
+      for (k, origin) in origins.iter().enumerate() {
+        match *origin {
+          (Some(a), Some(b)) => result.push(MoveEvent::Merged {
+            from: [cell(a), cell(b)],
+            numbers: [before[a], before[b]],
+            to: cell(k),
+            number: self.numbers[l[k].0][l[k].1],
+          }),
+          (Some(a), None) if a != k => result.push(MoveEvent::Moved {
+            from: cell(a),
+            to: cell(k),
+            number: before[a],
+          }),
+          _ => {}
+        }
+      }
+    }
+
+    self.best_score = max(self.best_score, self.score);
+    result
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::merge::{EqualTiles, MergeRules};
+  use crate::rng::GameRng;
+
+  #[test]
+  fn fiend_is_fail() {
+    let field = Field::from_numbers(vec![
+      vec![4, 32, 2, 4], vec![2, 32, 16, 4], vec![8, 4, 16, 32], vec![2, 4, 2, 8]
+    ]);
+    assert!(!field.fail());
+  }
+
+  #[test]
+  fn full_field_without_neighbours_is_fail() {
+    let field = Field::from_numbers(vec![
+      vec![2, 4, 8, 16], vec![32, 64, 128, 256], vec![2, 4, 8, 16], vec![32, 64, 128, 256]
+    ]);
+    assert!(field.fail());
+  }
+
+  #[test]
+  fn push_right_merges_towards_edge() {
+    let mut field = Field::from_numbers(vec![vec![2, 2, 4, 0], vec![0; 4], vec![0; 4], vec![0; 4]]);
+    assert!(!field.push_dir(Direction::Right).is_empty());
+    assert_eq!(field.get_numbers()[0], [0, 0, 4, 4]);
+  }
+
+  #[test]
+  fn obstacles_split_lines() {
+    let mut field = Field::from_numbers(vec![vec![2, 0, 2, 0, 4], vec![0, 2, 0, 0, 0]]);
+    field.set_rules(Rules {obstacles: vec![(1, 0), (3, 0)], ..Default::default()});
+    assert_eq!(field.get_empty_cells(), [(0, 1), (2, 1), (3, 1), (4, 1)]);
+    field.push_dir(Direction::Left);
+    assert_eq!(field.get_numbers(), &vec![vec![2, 0, 2, 0, 4], vec![2, 0, 0, 0, 0]]);
+    field.push_dir(Direction::Right);
+    assert_eq!(field.get_numbers(), &vec![vec![2, 0, 2, 0, 4], vec![0, 0, 0, 0, 2]]);
+    field.push_dir(Direction::Down);
+    assert_eq!(field.get_numbers(), &vec![vec![0, 0, 0, 0, 4], vec![2, 0, 2, 0, 2]]);
+    assert!(!field.can_push(Direction::UpRight));
+  }
+
+  #[test]
+  fn checked_rules() {
+    let mut field = Field::from_numbers(vec![vec![2, 0, 0], vec![0, 0, 0]]);
+    assert!(field.try_set_rules(Rules {obstacles: vec![(1, 1)], ..Default::default()}).is_ok());
+    assert!(field.try_set_rules(Rules {obstacles: vec![(0, 0)], ..Default::default()}).is_err());
+    assert!(field.try_set_rules(Rules {obstacles: vec![(3, 0)], ..Default::default()}).is_err());
+    assert_eq!(field.get_rules().obstacles, [(1, 1)]);
+  }
+
+  #[test]
+  fn wrapped_lines() {
+    let mut field = Field::from_numbers(vec![vec![2, 0, 0, 2], vec![4, 8, 4, 8], vec![0, 0, 0, 4]]);
+    field.set_rules(Rules {wrap: true, ..Default::default()});
+    field.push_dir(Direction::Right);
+    // The tile at the right edge goes over it, the full row doesn't move
+    assert_eq!(field.get_numbers(), &vec![vec![0, 4, 0, 0], vec![4, 8, 4, 8], vec![4, 0, 0, 0]]);
+    assert!(!field.push_dir(Direction::Down).is_empty());
+    assert_eq!(field.get_numbers(), &vec![vec![8, 0, 0, 0], vec![0, 4, 0, 0], vec![0, 8, 4, 8]]);
+
+    let mut field = Field::from_numbers(vec![vec![2, 0, 2, 2, 0]]);
+    field.set_rules(Rules {wrap: true, obstacles: vec![(1, 0)], ..Default::default()});
+    // The tile at the left edge goes around to the ones before the obstacle
+    field.push_dir(Direction::Left);
+    assert_eq!(field.get_numbers()[0], [0, 0, 4, 2, 0]);
+
+    // On a 3x2 board the diagonal ring takes all cells
+    let mut field = Field::from_numbers(vec![vec![2, 0, 0], vec![0, 0, 0]]);
+    field.set_rules(Rules {wrap: true, ..Default::default()});
+    assert_eq!(field.get_lines(Direction::DownRight).len(), 1);
+    field.push_dir(Direction::UpLeft);
+    assert_eq!(field.get_empty_cells().len(), 5);
+  }
+
+  #[test]
+  fn push_reports_events() {
+    let mut field = Field::from_numbers(vec![vec![0, 2, 0, 2], vec![0, 0, 4, 0], vec![8, 0, 0, 0]]);
+    let events = field.push_dir(Direction::Left);
+    assert_eq!(events, vec![
+      MoveEvent::Merged {from: [(1, 0), (3, 0)], numbers: [2, 2], to: (0, 0), number: 4},
+      MoveEvent::Moved {from: (2, 1), to: (0, 1), number: 4},
+    ]);
+    assert_eq!(events.iter().map(|e| e.get_score()).sum::<u64>(), field.get_score());
+
+    let mut rng = GameRng::new(1);
+    let events = field.add_item(Some(Direction::Left), &mut rng);
+    match events[..] {
+      [MoveEvent::Spawned {at: (x, y), number}] => assert_eq!(field.get_numbers()[y][x], number),
+      _ => panic!("{:?}", events),
+    }
+  }
+
+  #[test]
+  fn score_sums_merged_numbers() {
+    let mut field = Field::from_numbers(vec![vec![2, 2, 4, 4], vec![8, 0, 0, 8], vec![0; 4], vec![0; 4]]);
+    assert!(!field.push_dir(Direction::Left).is_empty());
+    assert_eq!(field.get_score(), 4 + 8 + 16);
+    assert_eq!(field.get_best_score(), 28);
+
+    let mut rng = GameRng::new(1);
+    field.new_game(&mut rng);
+    assert_eq!(field.get_score(), 0);
+    assert_eq!(field.get_best_score(), 28);
+  }
+
+  #[test]
+  fn reaching_target_wins() {
+    let mut field = Field::from_numbers(vec![vec![1024, 1024, 0], vec![2, 0, 0]]);
+    assert!(!field.is_won());
+    assert!(!field.push_dir(Direction::Left).is_empty());
+    assert!(field.is_won());
+    assert!(field.is_stopped());
+    field.keep_going();
+    assert!(!field.is_stopped());
+
+    let mut field = Field::from_numbers(vec![vec![1024, 1024, 0], vec![2, 0, 0]]);
+    field.set_rules(Rules {target: None, ..Default::default()});
+    assert!(!field.push_dir(Direction::Left).is_empty());
+    assert!(!field.is_won());
+  }
+
+  #[test]
+  fn push_on_rectangular_field() {
+    let mut field = Field::from_numbers(vec![
+      vec![2, 0, 0, 0, 0, 2], vec![0, 0, 0, 0, 0, 0], vec![0, 0, 0, 0, 0, 0], vec![2, 0, 0, 0, 0, 0]
+    ]);
+    assert_eq!(field.get_size(), (6, 4));
+    assert!(!field.push_dir(Direction::DownRight).is_empty());
+    assert_eq!(field.get_numbers()[3][3], 2);
+    assert_eq!(field.get_numbers()[0][5], 2);
+    assert!(!field.push_dir(Direction::Left).is_empty());
+    assert_eq!(field.get_numbers()[3][0], 4);
+  }
+
+  #[test]
+  fn push_with_other_merge_rules() {
+    let numbers = vec![vec![2, 2, 4, 0], vec![1, 2, 0, 0], vec![0; 4], vec![0; 4]];
+
+    let mut field = Field::from_numbers(numbers.clone());
+    field.set_rules(Rules {merge_rule: MergeRules::Fibonacci, ..Default::default()});
+    assert!(!field.push_dir(Direction::Left).is_empty());
+    assert_eq!(field.get_numbers()[0], [2, 2, 4, 0]);
+    assert_eq!(field.get_numbers()[1], [3, 0, 0, 0]);
+
+    let mut field = Field::from_numbers(numbers);
+    assert!(!field.push_dir_with(Direction::Left, &EqualTiles).is_empty());
+    assert_eq!(field.get_numbers()[0], [4, 4, 0, 0]);
+    assert_eq!(field.get_numbers()[1], [1, 2, 0, 0]);
+  }
+
+  #[test]
+  fn fail_follows_merge_rule() {
+    let mut field = Field::from_numbers(vec![vec![1, 3], vec![8, 32]]);
+    assert!(!field.fail());
+    field.set_rules(Rules {merge_rule: MergeRules::EqualTiles, ..Default::default()});
+    assert!(field.fail());
+  }
+
+  #[test]
+  fn directions_follow_rules() {
+    let numbers = vec![vec![2, 0, 0], vec![0, 2, 0], vec![0, 0, 0]];
+    let mut field = Field::from_numbers(numbers.clone());
+    field.set_rules(Rules {directions: DirectionSet::ORTHOGONAL, ..Default::default()});
+    assert!(field.push_dir(Direction::DownRight).is_empty());
+    assert_eq!(field.get_numbers(), &numbers);
+    assert!(!field.push_dir(Direction::Down).is_empty());
+    assert_eq!(field.get_numbers()[2], [2, 2, 0]);
+  }
+
+  #[test]
+  fn fail_follows_directions() {
+    let mut field = Field::from_numbers(vec![vec![2, 4], vec![4, 2]]);
+    assert!(!field.fail());
+    field.set_rules(Rules {directions: DirectionSet::ORTHOGONAL, ..Default::default()});
+    assert!(field.fail());
+  }
+
+  #[test]
+  fn legal_moves_match_push() {
+    let numbers = vec![vec![2, 4, 8], vec![4, 8, 2], vec![0, 2, 4]];
+    let field = Field::from_numbers(numbers.clone());
+    let legal = field.legal_moves();
+    for dir in Direction::ALL.iter() {
+      let mut copy = field.clone();
+      assert_eq!(legal.contains(*dir), !copy.push_dir(*dir).is_empty(), "{:?}", dir);
+    }
+    assert!(!legal.contains(Direction::Up));
+    assert!(legal.contains(Direction::Down));
+
+    let mut rng = GameRng::new(11);
+    for merge_rule in MergeRules::ALL.iter() {
+      for _ in 0 .. 200 {
+        let numbers = (0 .. 4).map(
+          |_| (0 .. 5).map(|_| [0, 1, 2, 3, 4, 6][crate::rng::gen_below(&mut rng, 6)]).collect()
+        ).collect();
+        let mut field = Field::from_numbers(numbers);
+        field.set_rules(Rules {merge_rule: *merge_rule, ..Default::default()});
+        let legal = field.legal_moves();
+        for dir in Direction::ALL.iter() {
+          let mut copy = field.clone();
+          assert_eq!(legal.contains(*dir), !copy.push_dir(*dir).is_empty());
+        }
+      }
+    }
+  }
+
+  #[test]
+  fn empty_cells_without_moves_is_fail() {
+    let mut field = Field::from_numbers(vec![vec![0, 2], vec![4, 0]]);
+    field.set_rules(Rules {directions: DirectionSet::DIAGONAL, ..Default::default()});
+    assert!(field.legal_moves().is_empty());
+    assert!(field.fail());
+  }
+
+  #[test]
+  fn push_diagonal_follows_diagonal_lines() {
+    let mut field = Field::from_numbers(vec![
+      vec![2, 0, 0, 0], vec![0, 2, 0, 0], vec![0, 0, 0, 0], vec![0, 0, 0, 4]
+    ]);
+    assert!(!field.push_dir(Direction::DownRight).is_empty());
+    assert_eq!(field.get_numbers()[3][3], 4);
+    assert_eq!(field.get_numbers()[2][2], 4);
+    assert!(!field.push_dir(Direction::DownRight).is_empty());
+    assert_eq!(field.get_numbers()[3][3], 8);
+  }
+
+  #[test]
+  fn new_game_spawns_two_items() {
+    let mut rng = GameRng::new(1);
+    let mut field = Field::with_size(5, 3);
+    field.new_game(&mut rng);
+    assert_eq!(field.get_size(), (5, 3));
+    let count = field.get_numbers().iter().flatten().filter(|n| **n != 0).count();
+    assert_eq!(count, 2);
+    assert!(!field.is_game_over());
+  }
+}
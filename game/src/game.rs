@@ -0,0 +1,331 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use crate::direction::Direction;
+use crate::event::MoveEvent;
+use crate::field::Field;
+use crate::rng::GameRng;
+use crate::rules::Rules;
+
+pub const DEFAULT_HISTORY_LIMIT: usize = 100;
+
+/// State to return to on undo or redo, the generator goes back too
+/// so redone moves spawn the same tiles
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Snapshot {
+  pub(crate) field: Field,
+  pub(crate) rng: GameRng,
+}
+
+/// A field together with the seed and the random generator it was played with.
+/// The same rules, seed and moves always give the same game
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Game {
+  pub(crate) field: Field,
+  pub(crate) seed: u64,
+  pub(crate) rng: GameRng,
+  pub(crate) undo: VecDeque<Snapshot>,
+  pub(crate) redo: Vec<Snapshot>,
+  pub(crate) history_limit: usize,
+  /// Moves from the start of the game to the current state
+  pub(crate) moves: Vec<Direction>,
+  /// Moves of the redo stack, the next one last
+  pub(crate) undone_moves: Vec<Direction>,
+  /// Field the game started from if it was not spawned from the seed
+  pub(crate) start: Option<Field>,
+  /// Time spent playing, counted by the player of the game
+  pub(crate) play_time: Duration,
+  /// Some moves were made by a computer player
+  pub(crate) assisted: bool,
+}
+
+impl Game {
+  pub fn new(width: usize, height: usize, rules: Rules, seed: u64) -> Self {
+    let mut game = Game {
+      field: Field::with_rules(width, height, rules),
+      seed,
+      rng: GameRng::new(seed),
+      undo: VecDeque::new(),
+      redo: Vec::new(),
+      history_limit: DEFAULT_HISTORY_LIMIT,
+      moves: Vec::new(),
+      undone_moves: Vec::new(),
+      start: None,
+      play_time: Duration::ZERO,
+      assisted: false,
+    };
+    game.field.new_game(&mut game.rng);
+    game
+  }
+
+  /// Starts from the given field, keeping its rules and score, instead of the first spawns
+  pub fn with_field(field: Field, seed: u64) -> Self {
+    Game {
+      start: Some(field.clone()),
+      field,
+      seed,
+      rng: GameRng::new(seed),
+      undo: VecDeque::new(),
+      redo: Vec::new(),
+      history_limit: DEFAULT_HISTORY_LIMIT,
+      moves: Vec::new(),
+      undone_moves: Vec::new(),
+      play_time: Duration::ZERO,
+      assisted: false,
+    }
+  }
+
+  /// Starts over with the same size and rules
+  pub fn new_game(&mut self, seed: u64) {
+    self.seed = seed;
+    self.rng = GameRng::new(seed);
+    self.field.new_game(&mut self.rng);
+    self.undo.clear();
+    self.redo.clear();
+    self.moves.clear();
+    self.undone_moves.clear();
+    self.start = None;
+    self.play_time = Duration::ZERO;
+    self.assisted = false;
+  }
+
+  pub fn get_field(&self) -> &Field {
+    &self.field
+  }
+
+  pub fn get_seed(&self) -> u64 {
+    self.seed
+  }
+
+  pub fn get_rng(&self) -> &GameRng {
+    &self.rng
+  }
+
+  /// Moves that lead from the start to the current field, undone ones excluded
+  pub fn get_moves(&self) -> &[Direction] {
+    &self.moves
+  }
+
+  /// The first field of a game started by `with_field`, `None` if it was spawned from the seed
+  pub fn get_start(&self) -> Option<&Field> {
+    self.start.as_ref()
+  }
+
+  pub fn get_play_time(&self) -> Duration {
+    self.play_time
+  }
+
+  /// Counts time spent on the game, it is kept in saves
+  pub fn add_play_time(&mut self, time: Duration) {
+    self.play_time += time;
+  }
+
+  /// Whether a computer player made some of the moves
+  pub fn is_assisted(&self) -> bool {
+    self.assisted
+  }
+
+  pub fn set_assisted(&mut self) {
+    self.assisted = true;
+  }
+
+  /// How many moves can be undone, older ones are forgotten. Undone moves beyond the limit
+  /// can't be redone any more, the farthest ones go
+  pub fn set_history_limit(&mut self, limit: usize) {
+    self.history_limit = limit;
+    while self.undo.len() > limit {
+      self.undo.pop_front();
+    }
+    self.redo.drain(.. self.redo.len().saturating_sub(limit));
+    self.undone_moves.drain(.. self.undone_moves.len().saturating_sub(limit));
+  }
+
+  pub fn can_undo(&self) -> bool {
+    !self.undo.is_empty()
+  }
+
+  pub fn can_redo(&self) -> bool {
+    !self.redo.is_empty()
+  }
+
+  /// Takes back the last move together with the tiles it spawned
+  pub fn undo(&mut self) -> bool {
+    match self.undo.pop_back() {
+      Some(snapshot) => {
+        let current = self.restore(snapshot);
+        self.redo.push(current);
+        if let Some(dir) = self.moves.pop() {
+          self.undone_moves.push(dir);
+        }
+        true
+      }
+      None => false,
+    }
+  }
+
+  /// Repeats the last undone move, spawning the same tiles as before
+  pub fn redo(&mut self) -> bool {
+    match self.redo.pop() {
+      Some(snapshot) => {
+        let current = self.restore(snapshot);
+        if self.undo.len() == self.history_limit {
+          self.undo.pop_front();
+        }
+        self.undo.push_back(current);
+        if let Some(dir) = self.undone_moves.pop() {
+          self.moves.push(dir);
+        }
+        true
+      }
+      None => false,
+    }
+  }
+
+  /// Replaces the state by `snapshot`, returns the replaced one
+  fn restore(&mut self, snapshot: Snapshot) -> Snapshot {
+    let best_score = self.field.get_best_score();
+    let field = std::mem::replace(&mut self.field, snapshot.field);
+    let rng = std::mem::replace(&mut self.rng, snapshot.rng);
+    self.field.raise_best_score(best_score);
+    Snapshot {field, rng}
+  }
+
+  pub fn keep_going(&mut self) {
+    self.field.keep_going();
+  }
+
+  /// Pushes tiles and spawns new ones. Returns nothing if the move changed nothing
+  /// or the game is stopped by game over or a win
+  pub fn make_move(&mut self, dir: Direction) -> Vec<MoveEvent> {
+    if self.field.is_stopped() {
+      return Vec::new();
+    }
+
+    let before = Snapshot {field: self.field.clone(), rng: self.rng};
+    let mut events = self.field.push_dir(dir);
+    if events.is_empty() {
+      return events;
+    }
+
+    events.extend(self.field.add_item(Some(dir), &mut self.rng));
+    if self.history_limit > 0 {
+      if self.undo.len() == self.history_limit {
+        self.undo.pop_front();
+      }
+      self.undo.push_back(before);
+    }
+    self.redo.clear();
+    self.moves.push(dir);
+    self.undone_moves.clear();
+    events
+  }
+}
+
+/// Tries the directions of `Direction::ALL` in turn, `tries` times or until the game is over,
+/// going on after a win. `on_move` gets the events of every try. The games of the tests come from here
+#[cfg(test)]
+pub(crate) fn play_in_turn(game: &mut Game, tries: usize, mut on_move: impl FnMut(Direction, &[MoveEvent])) {
+  for dir in Direction::ALL.iter().cycle().take(tries) {
+    if game.get_field().is_won() && !game.get_field().is_keep_going() {
+      game.keep_going();
+    }
+    if game.get_field().is_stopped() {
+      break;
+    }
+    let events = game.make_move(*dir);
+    on_move(*dir, &events);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::direction::DirectionSet;
+
+  fn play(seed: u64) -> Game {
+    let mut game = Game::new(4, 4, Rules::default(), seed);
+    play_in_turn(&mut game, 40, |_, _| {});
+    game
+  }
+
+  #[test]
+  fn same_seed_same_game() {
+    assert_eq!(play(12345), play(12345));
+    assert_ne!(play(12345).get_field(), play(54321).get_field());
+  }
+
+  #[test]
+  fn seeded_game_is_stable() {
+    // Fails if the generator or the spawn code changed, bump RNG_VERSION then
+    let game = Game::new(4, 4, Rules::default(), 2048);
+    assert_eq!(game.get_field().get_numbers(), &vec![
+      vec![0, 4, 0, 0], vec![0, 0, 0, 0], vec![0, 0, 0, 0], vec![0, 0, 0, 2]
+    ]);
+  }
+
+  #[test]
+  fn undo_and_redo() {
+    let mut game = Game::new(4, 4, Rules::default(), 3);
+    let start = game.get_field().clone();
+    assert!(!game.undo());
+
+    let mut dir = Direction::ALL.iter().cycle();
+    while game.make_move(*dir.next().unwrap()).is_empty() {}
+    let after_first = game.get_field().clone();
+    while game.make_move(*dir.next().unwrap()).is_empty() {}
+    let after_second = game.clone();
+
+    assert!(game.undo());
+    assert_eq!(game.get_field().get_numbers(), after_first.get_numbers());
+    assert_eq!(game.get_field().get_score(), after_first.get_score());
+    assert_eq!(game.get_field().get_best_score(), after_second.get_field().get_best_score());
+    assert!(game.undo());
+    assert_eq!(game.get_field().get_numbers(), start.get_numbers());
+    assert!(!game.can_undo());
+
+    assert!(game.get_moves().is_empty());
+
+    assert!(game.redo());
+    assert!(game.redo());
+    assert!(!game.redo());
+    assert_eq!(game.get_field(), after_second.get_field());
+    assert_eq!(game.get_rng(), after_second.get_rng());
+    assert_eq!(game.get_moves(), after_second.get_moves());
+    assert_eq!(game.get_moves().len(), 2);
+  }
+
+  #[test]
+  fn history_is_bounded() {
+    let mut game = Game::new(4, 4, Rules::default(), 3);
+    game.set_history_limit(2);
+    let mut moves = 0;
+    for dir in Direction::ALL.iter().cycle().take(20) {
+      if !game.make_move(*dir).is_empty() {
+        moves += 1;
+      }
+    }
+    assert!(moves > 2);
+    assert!(game.undo());
+    assert!(game.undo());
+    assert!(!game.undo());
+
+    // A smaller limit drops the farthest undone move, redone moves keep to it too
+    let moves = game.get_moves().to_vec();
+    game.set_history_limit(1);
+    assert!(game.redo());
+    assert!(!game.redo());
+    assert_eq!(game.get_moves().len(), moves.len() + 1);
+    assert!(game.undo());
+    assert!(!game.undo());
+    assert_eq!(game.get_moves(), &moves[..]);
+  }
+
+  #[test]
+  fn illegal_move_keeps_rng() {
+    let rules = Rules {directions: DirectionSet::ORTHOGONAL, ..Default::default()};
+    let mut game = Game::new(4, 4, rules, 1);
+    let rng = *game.get_rng();
+    assert!(game.make_move(Direction::UpLeft).is_empty());
+    assert_eq!(*game.get_rng(), rng);
+  }
+}
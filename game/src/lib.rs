@@ -0,0 +1,16 @@
+pub mod ai;
+pub mod args;
+pub mod bitboard;
+pub mod direction;
+pub mod event;
+pub mod field;
+pub mod game;
+pub mod merge;
+pub mod notation;
+pub mod replay;
+pub mod rng;
+pub mod rules;
+pub mod save;
+pub mod scores;
+pub mod spawn;
+pub mod statistics;
@@ -0,0 +1,176 @@
+/// Decides which tiles join when one slides onto another
+pub trait MergeRule {
+  /// Value of the tile produced when `moving` slides onto `standing`, `None` if they don't merge
+  fn merge(&self, standing: i32, moving: i32) -> Option<i32>;
+
+  /// Numbers new tiles are chosen from
+  fn start_numbers(&self) -> &'static [i32];
+}
+
+/// Merge when the sum is a power of two, the original diagonal 2048 rule
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PowerOfTwoSum;
+
+impl MergeRule for PowerOfTwoSum {
+  fn merge(&self, standing: i32, moving: i32) -> Option<i32> {
+    let sum = standing + moving;
+    if sum & (sum - 1) == 0 {
+      Some(sum)
+    } else {
+      None
+    }
+  }
+
+  fn start_numbers(&self) -> &'static [i32] {
+    &[2, 4]
+  }
+}
+
+/// Classic 2048: only equal tiles merge
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct EqualTiles;
+
+impl MergeRule for EqualTiles {
+  fn merge(&self, standing: i32, moving: i32) -> Option<i32> {
+    if standing == moving {
+      Some(standing + moving)
+    } else {
+      None
+    }
+  }
+
+  fn start_numbers(&self) -> &'static [i32] {
+    &[2, 4]
+  }
+}
+
+/// Adjacent Fibonacci numbers merge into the next one: 1+1, 1+2, 2+3, 3+5...
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Fibonacci;
+
+impl MergeRule for Fibonacci {
+  fn merge(&self, standing: i32, moving: i32) -> Option<i32> {
+    let (low, high) = if standing < moving { (standing, moving) } else { (moving, standing) };
+    let (mut a, mut b) = (1, 1);
+    while b < high {
+      let c = a + b;
+      a = b;
+      b = c;
+    }
+
+    if a == low && b == high {
+      Some(low + high)
+    } else {
+      None
+    }
+  }
+
+  fn start_numbers(&self) -> &'static [i32] {
+    &[1, 2]
+  }
+}
+
+/// Threes: 1 and 2 make 3, tiles from 3 up merge with equal ones
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Threes;
+
+impl MergeRule for Threes {
+  fn merge(&self, standing: i32, moving: i32) -> Option<i32> {
+    if standing + moving == 3 && standing != moving {
+      Some(3)
+    } else if standing >= 3 && standing == moving {
+      Some(standing + moving)
+    } else {
+      None
+    }
+  }
+
+  fn start_numbers(&self) -> &'static [i32] {
+    &[1, 2, 3]
+  }
+}
+
+/// Built-in rules, selectable per game
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum MergeRules {
+  #[default]
+  PowerOfTwoSum,
+  EqualTiles,
+  Fibonacci,
+  Threes,
+}
+
+impl MergeRules {
+  pub const ALL: [MergeRules; 4] = [
+    MergeRules::PowerOfTwoSum, MergeRules::EqualTiles, MergeRules::Fibonacci, MergeRules::Threes,
+  ];
+
+  pub fn get_name(self) -> &'static str {
+    match self {
+      MergeRules::PowerOfTwoSum => "pow2sum",
+      MergeRules::EqualTiles => "classic",
+      MergeRules::Fibonacci => "fibonacci",
+      MergeRules::Threes => "threes",
+    }
+  }
+
+  pub fn from_name(name: &str) -> Option<Self> {
+    Self::ALL.iter().copied().find(|r| r.get_name() == name)
+  }
+
+  fn as_rule(&self) -> &dyn MergeRule {
+    match self {
+      MergeRules::PowerOfTwoSum => &PowerOfTwoSum,
+      MergeRules::EqualTiles => &EqualTiles,
+      MergeRules::Fibonacci => &Fibonacci,
+      MergeRules::Threes => &Threes,
+    }
+  }
+}
+
+impl MergeRule for MergeRules {
+  fn merge(&self, standing: i32, moving: i32) -> Option<i32> {
+    self.as_rule().merge(standing, moving)
+  }
+
+  fn start_numbers(&self) -> &'static [i32] {
+    self.as_rule().start_numbers()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn power_of_two_sum() {
+    assert_eq!(PowerOfTwoSum.merge(4, 4), Some(8));
+    assert_eq!(PowerOfTwoSum.merge(2, 4), None);
+  }
+
+  #[test]
+  fn fibonacci_merges_neighbours_only() {
+    assert_eq!(Fibonacci.merge(1, 1), Some(2));
+    assert_eq!(Fibonacci.merge(2, 1), Some(3));
+    assert_eq!(Fibonacci.merge(5, 8), Some(13));
+    assert_eq!(Fibonacci.merge(2, 2), None);
+    assert_eq!(Fibonacci.merge(3, 8), None);
+    assert_eq!(Fibonacci.merge(4, 4), None);
+  }
+
+  #[test]
+  fn threes() {
+    assert_eq!(Threes.merge(1, 2), Some(3));
+    assert_eq!(Threes.merge(1, 1), None);
+    assert_eq!(Threes.merge(2, 2), None);
+    assert_eq!(Threes.merge(6, 6), Some(12));
+    assert_eq!(Threes.merge(3, 6), None);
+  }
+
+  #[test]
+  fn names_round_trip() {
+    for rule in MergeRules::ALL.iter() {
+      assert_eq!(MergeRules::from_name(rule.get_name()), Some(*rule));
+    }
+  }
+}
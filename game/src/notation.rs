@@ -0,0 +1,179 @@
+//! One line text for a position, to paste into bug reports and tests:
+//!
+//! ```text
+//! WIDTHxHEIGHT ROWS SIDE SCORE
+//! 4x4 4,32,2,4/2,32,16,4/8,4,16,32/2,4,2,8 p 0
+//! 3x3 .4./2#./..8 s 12
+//! ```
+//!
+//! Rows go from top to bottom separated by `/`. In a row `.` is an empty cell, `#` an obstacle,
+//! `,` separates two numbers next to each other. SIDE is `p` when the player moves next
+//! and `s` when a tile spawns next. Rules other than obstacles are not a part of the notation
+
+use crate::field::Field;
+use crate::game::Game;
+use crate::rng::GameRng;
+use crate::rules::Rules;
+
+/// Number of an obstacle cell while a row is read
+const OBSTACLE: i32 = -1;
+
+/// Who acts next
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+  #[default]
+  Player,
+  Spawn,
+}
+
+pub fn write_position(field: &Field, side: Side) -> String {
+  let (width, height) = field.get_size();
+  let rows: Vec<String> = field.get_numbers().iter().enumerate().map(|(y, line)| {
+    let mut row = String::new();
+    let mut after_number = false;
+    for (x, n) in line.iter().enumerate() {
+      if field.get_rules().is_obstacle((x, y)) {
+        row.push('#');
+        after_number = false;
+      } else if *n == 0 {
+        row.push('.');
+        after_number = false;
+      } else {
+        if after_number {
+          row.push(',');
+        }
+        row.push_str(&n.to_string());
+        after_number = true;
+      }
+    }
+    row
+  }).collect();
+
+  let side = match side {
+    Side::Player => 'p',
+    Side::Spawn => 's',
+  };
+  format!("{}x{} {} {} {}", width, height, rows.join("/"), side, field.get_score())
+}
+
+fn read_row(row: &str) -> Result<Vec<i32>, String> {
+  let mut result = Vec::new();
+  let mut number = String::new();
+  let finish = |number: &mut String, result: &mut Vec<i32>| -> Result<(), String> {
+    if !number.is_empty() {
+      let n: i32 = number.parse().map_err(|_| format!("bad number {}", number))?;
+      if n <= 0 {
+        return Err(format!("bad number {}", number));
+      }
+      result.push(n);
+      number.clear();
+    }
+    Ok(())
+  };
+
+  for c in row.chars() {
+    match c {
+      '0' ..= '9' => number.push(c),
+      '.' => {
+        finish(&mut number, &mut result)?;
+        result.push(0);
+      }
+      '#' => {
+        finish(&mut number, &mut result)?;
+        result.push(OBSTACLE);
+      }
+      ',' if !number.is_empty() => finish(&mut number, &mut result)?,
+      c => return Err(format!("unexpected '{}' in row {}", c, row)),
+    }
+  }
+  finish(&mut number, &mut result)?;
+  Ok(result)
+}
+
+/// The field with the default rules, its obstacles and the score, and who acts next
+pub fn read_position(text: &str) -> Result<(Field, Side), String> {
+  let words: Vec<&str> = text.split_whitespace().collect();
+  if words.len() != 4 {
+    return Err("expected size, rows, side and score".to_string());
+  }
+
+  let size = words[0].split_once('x')
+    .and_then(|(w, h)| Some((w.parse::<usize>().ok()?, h.parse::<usize>().ok()?)))
+    .ok_or_else(|| format!("bad size {}", words[0]))?;
+  let mut numbers = words[1].split('/').map(read_row).collect::<Result<Vec<_>, _>>()?;
+  if size.0 == 0 || numbers.len() != size.1 || numbers.iter().any(|row| row.len() != size.0) {
+    return Err(format!("rows don't match the size {}", words[0]));
+  }
+
+  let side = match words[2] {
+    "p" => Side::Player,
+    "s" => Side::Spawn,
+    side => return Err(format!("bad side {}", side)),
+  };
+  let mut obstacles = Vec::new();
+  for (y, row) in numbers.iter_mut().enumerate() {
+    for (x, n) in row.iter_mut().enumerate() {
+      if *n == OBSTACLE {
+        obstacles.push((x, y));
+        *n = 0;
+      }
+    }
+  }
+  let mut field = Field::from_numbers(numbers);
+  field.try_set_rules(Rules {obstacles, ..Default::default()})?;
+  field.score = words[3].parse().map_err(|_| format!("bad score {}", words[3]))?;
+  Ok((field, side))
+}
+
+/// A game from the position, the pending spawn comes from the seed
+pub fn start_game(mut field: Field, side: Side, seed: u64) -> Game {
+  if side == Side::Spawn {
+    // Another stream than the one of the game, which starts after the spawn
+    field.add_item(None, &mut GameRng::with_stream(seed, 1));
+  }
+  Game::with_field(field, seed)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::direction::Direction;
+
+  #[test]
+  fn fixtures() {
+    let (field, side) = read_position("4x4 4,32,2,4/2,32,16,4/8,4,16,32/2,4,2,8 p 0").unwrap();
+    assert_eq!(side, Side::Player);
+    assert_eq!(field.get_numbers()[1], [2, 32, 16, 4]);
+    assert!(!field.fail());
+  }
+
+  #[test]
+  fn round_trip() {
+    let text = "5x3 .16.../2.#.1024/8,4,2#. s 1234";
+    let (field, side) = read_position(text).unwrap();
+    assert_eq!(field.get_numbers()[2], [8, 4, 2, 0, 0]);
+    assert_eq!(field.get_rules().obstacles, [(2, 1), (3, 2)]);
+    assert_eq!(field.get_score(), 1234);
+    assert_eq!(write_position(&field, side), text);
+  }
+
+  #[test]
+  fn errors() {
+    assert!(read_position("4x4 .... p 0").is_err());
+    assert!(read_position("2x1 2,,4 p 0").is_err());
+    assert!(read_position("2x1 2,4 x 0").is_err());
+    assert!(read_position("2x1 2,0 p 0").is_err());
+    assert!(read_position("2x1 -2,4 p 0").is_err());
+  }
+
+  #[test]
+  fn custom_start() {
+    let (field, side) = read_position("3x3 .../.../2.. s 0").unwrap();
+    let mut game = start_game(field, side, 5);
+    assert_eq!(game.get_field().get_empty_cells().len(), 7);
+    assert_eq!(game, start_game(read_position("3x3 .../.../2.. s 0").unwrap().0, side, 5));
+    let (field, side) = read_position("3x3 .../.../2.. p 0").unwrap();
+    assert_eq!(start_game(field, side, 5).get_field().get_empty_cells().len(), 8);
+    assert!(!game.make_move(Direction::Up).is_empty() || !game.make_move(Direction::Right).is_empty());
+  }
+}
@@ -0,0 +1,229 @@
+//! A game as its rules, seed and moves. Pushes and spawns are deterministic,
+//! so the moves are enough to play the game again. Replay files are text
+//! in the style of saves:
+//!
+//! ```text
+//! 2048-replay 1
+//! size, merge, directions, target, spawn, obstacles, wrap, rng, seed and start lines as in a save
+//! moves DIGITS|-      numpad digits of the moves, - for none
+//! end
+//! ```
+//!
+//! A reader refuses versions other than its own
+
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::direction::Direction;
+use crate::event::MoveEvent;
+use crate::game::Game;
+use crate::rules::Rules;
+use crate::field::Field;
+use crate::save::{invalid, moves_to_string, parse, parse_moves, read_header, write_header, Header, Lines};
+
+pub const REPLAY_MAGIC: &str = "2048-replay";
+pub const REPLAY_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Replay {
+  pub width: usize,
+  pub height: usize,
+  pub rules: Rules,
+  pub seed: u64,
+  /// Field of a game from a custom position, `None` if the seed spawned the first tiles
+  pub start: Option<Field>,
+  pub moves: Vec<Direction>,
+}
+
+impl Replay {
+  /// Moves that lead to the current field of the game
+  pub fn from_game(game: &Game) -> Self {
+    let (width, height) = game.get_field().get_size();
+    Replay {
+      width,
+      height,
+      rules: game.get_field().get_rules().clone(),
+      seed: game.get_seed(),
+      start: game.get_start().cloned(),
+      moves: game.get_moves().to_vec(),
+    }
+  }
+
+  /// The game before the first move
+  pub fn start(&self) -> Game {
+    match &self.start {
+      Some(field) => Game::with_field(field.clone(), self.seed),
+      None => Game::new(self.width, self.height, self.rules.clone(), self.seed),
+    }
+  }
+
+  /// The game after the first `count` moves
+  pub fn play_to(&self, count: usize) -> Game {
+    let mut game = self.start();
+    for dir in &self.moves[.. count.min(self.moves.len())] {
+      play_move(&mut game, *dir);
+    }
+    game
+  }
+
+  pub fn to_text(&self) -> String {
+    let mut text = String::new();
+    writeln!(text, "{} {}", REPLAY_MAGIC, REPLAY_VERSION).unwrap();
+    write_header(&mut text, (self.width, self.height), &self.rules, self.seed, self.start.as_ref());
+    writeln!(text, "moves {}", moves_to_string(&self.moves)).unwrap();
+    writeln!(text, "end").unwrap();
+    text
+  }
+
+  pub fn from_text(text: &str) -> io::Result<Self> {
+    let mut lines = Lines::new(text);
+    let header = lines.expect(REPLAY_MAGIC, Some(1))?;
+    let version: u32 = parse(header[0])?;
+    if version != REPLAY_VERSION {
+      return Err(invalid(format!("unsupported replay version {}", version)));
+    }
+
+    let Header {size: (width, height), rules, seed, start} = read_header(&mut lines)?;
+    let moves = parse_moves(lines.expect("moves", Some(1))?[0])?;
+    lines.expect("end", Some(0))?;
+    Ok(Replay {width, height, rules, seed, start, moves})
+  }
+
+  pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+    fs::write(path, self.to_text())
+  }
+
+  pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+    Self::from_text(&fs::read_to_string(path)?)
+  }
+}
+
+/// A recorded move, continuing past a win the way the player did
+fn play_move(game: &mut Game, dir: Direction) -> Vec<MoveEvent> {
+  if game.get_field().is_won() && !game.get_field().is_keep_going() {
+    game.keep_going();
+  }
+  game.make_move(dir)
+}
+
+/// Steps through a replay in both directions
+#[derive(Debug, Clone)]
+pub struct Playback {
+  replay: Replay,
+  game: Game,
+}
+
+impl Playback {
+  pub fn new(replay: Replay) -> Self {
+    let mut game = replay.start();
+    // Every step can be taken back without playing from the start
+    game.set_history_limit(replay.moves.len());
+    Playback {replay, game}
+  }
+
+  pub fn get_replay(&self) -> &Replay {
+    &self.replay
+  }
+
+  /// The game after the moves played so far
+  pub fn get_game(&self) -> &Game {
+    &self.game
+  }
+
+  /// Moves played so far
+  pub fn get_position(&self) -> usize {
+    self.game.get_moves().len()
+  }
+
+  pub fn is_at_end(&self) -> bool {
+    self.get_position() >= self.replay.moves.len()
+  }
+
+  /// Plays the next move, returns nothing at the end
+  pub fn step_forward(&mut self) -> Vec<MoveEvent> {
+    match self.replay.moves.get(self.get_position()) {
+      Some(dir) => play_move(&mut self.game, *dir),
+      None => Vec::new(),
+    }
+  }
+
+  /// Takes back the last move, false at the start
+  pub fn step_back(&mut self) -> bool {
+    self.game.undo()
+  }
+
+  pub fn seek(&mut self, position: usize) {
+    while self.get_position() > position && self.step_back() {}
+    while self.get_position() < position && !self.step_forward().is_empty() {}
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::direction::DirectionSet;
+  use crate::game::play_in_turn;
+  use crate::notation::{read_position, start_game};
+
+  fn played_game() -> Game {
+    let rules = Rules {directions: DirectionSet::DIAGONAL, target: Some(16), ..Default::default()};
+    let mut game = Game::new(4, 4, rules, 9);
+    play_in_turn(&mut game, 60, |_, _| {});
+    game.undo();
+    game
+  }
+
+  #[test]
+  fn replay_reaches_the_same_field() {
+    let game = played_game();
+    let replay = Replay::from_game(&game);
+    assert!(replay.moves.len() > 10);
+    let replayed = replay.play_to(replay.moves.len());
+    assert_eq!(replayed.get_field().get_numbers(), game.get_field().get_numbers());
+    assert_eq!(replayed.get_field().get_score(), game.get_field().get_score());
+  }
+
+  #[test]
+  fn text_round_trip() {
+    let replay = Replay::from_game(&played_game());
+    let text = replay.to_text();
+    assert!(text.starts_with("2048-replay 1\nsize 4 4\n"));
+    assert_eq!(Replay::from_text(&text).unwrap(), replay);
+    assert!(Replay::from_text(&text.replace("2048-replay 1", "2048-replay 2")).is_err());
+    assert!(Replay::from_text(&text.replace("\nrng 1\n", "\nrng 2\n")).is_err());
+    assert!(Replay::from_text(&text.replace("moves ", "moves 5")).is_err());
+  }
+
+  #[test]
+  fn playback_steps() {
+    let replay = Replay::from_game(&played_game());
+    let mut playback = Playback::new(replay.clone());
+    assert!(!playback.step_back());
+    while !playback.step_forward().is_empty() {}
+    assert!(playback.is_at_end());
+    assert_eq!(playback.get_game().get_field(), replay.play_to(usize::MAX).get_field());
+
+    playback.seek(5);
+    assert_eq!(playback.get_position(), 5);
+    playback.seek(0);
+    assert_eq!(playback.get_game().get_field().get_numbers(), replay.start().get_field().get_numbers());
+    playback.seek(5);
+    assert_eq!(playback.get_position(), 5);
+    assert_eq!(playback.get_game().get_field().get_numbers(), replay.play_to(5).get_field().get_numbers());
+  }
+
+  #[test]
+  fn custom_start() {
+    let (field, side) = read_position("4x4 2.../.4../..8./...2 s 100").unwrap();
+    let mut game = start_game(field, side, 3);
+    for dir in Direction::ALL.iter().cycle().take(20) {
+      game.make_move(*dir);
+    }
+
+    let replay = Replay::from_text(&Replay::from_game(&game).to_text()).unwrap();
+    assert!(replay.start.is_some());
+    assert_eq!(replay.play_to(usize::MAX).get_field(), game.get_field());
+  }
+}
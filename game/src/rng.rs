@@ -0,0 +1,117 @@
+use rand::{Error, RngCore};
+
+/// Bumped whenever `GameRng` or `gen_below` would produce a different sequence for the same seed,
+/// saved games and replays with another version can't be reproduced
+pub const RNG_VERSION: u32 = 1;
+
+const DEFAULT_STREAM: u64 = 54;
+const MULTIPLIER: u64 = 6364136223846793005;
+
+/// PCG32 (XSH RR), fully specified here so the same seed gives the same game
+/// on every platform and with every version of `rand`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GameRng {
+  state: u64,
+  inc: u64,
+}
+
+impl GameRng {
+  pub fn new(seed: u64) -> Self {
+    Self::with_stream(seed, DEFAULT_STREAM)
+  }
+
+  /// Same as `pcg32_srandom_r` from the reference implementation
+  pub fn with_stream(seed: u64, stream: u64) -> Self {
+    let mut rng = GameRng {state: 0, inc: (stream << 1) | 1};
+    rng.step();
+    rng.state = rng.state.wrapping_add(seed);
+    rng.step();
+    rng
+  }
+
+  /// Internal state, for saving: (state, increment)
+  pub fn get_state(&self) -> (u64, u64) {
+    (self.state, self.inc)
+  }
+
+  pub fn from_state(state: u64, inc: u64) -> Self {
+    GameRng {state, inc: inc | 1}
+  }
+
+  fn step(&mut self) {
+    self.state = self.state.wrapping_mul(MULTIPLIER).wrapping_add(self.inc);
+  }
+}
+
+impl RngCore for GameRng {
+  fn next_u32(&mut self) -> u32 {
+    let old = self.state;
+    self.step();
+    let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+    let rot = (old >> 59) as u32;
+    xorshifted.rotate_right(rot)
+  }
+
+  fn next_u64(&mut self) -> u64 {
+    let low = self.next_u32() as u64;
+    let high = self.next_u32() as u64;
+    (high << 32) | low
+  }
+
+  fn fill_bytes(&mut self, dest: &mut [u8]) {
+    for chunk in dest.chunks_mut(4) {
+      let bytes = self.next_u32().to_le_bytes();
+      chunk.copy_from_slice(&bytes[.. chunk.len()]);
+    }
+  }
+
+  fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+    self.fill_bytes(dest);
+    Ok(())
+  }
+}
+
+/// Uniform number in `0 .. bound`. Unlike `Rng::gen_range` the algorithm is fixed here,
+/// so game code uses this one to stay reproducible
+pub fn gen_below<R: RngCore + ?Sized>(rng: &mut R, bound: usize) -> usize {
+  assert!(bound > 0 && bound <= u32::MAX as usize);
+  let bound = bound as u32;
+  let threshold = bound.wrapping_neg() % bound;
+  loop {
+    let r = rng.next_u32();
+    if r >= threshold {
+      return (r % bound) as usize;
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn matches_reference_pcg32() {
+    let mut rng = GameRng::with_stream(42, 54);
+    let expected = [0xa15c02b7, 0x7b47f409, 0xba1d3330, 0x83d2f293, 0xbfa4784b, 0xcbed606e];
+    for e in expected.iter() {
+      assert_eq!(rng.next_u32(), *e);
+    }
+  }
+
+  #[test]
+  fn gen_below_is_in_range() {
+    let mut rng = GameRng::new(1);
+    for bound in 1 .. 100 {
+      assert!(gen_below(&mut rng, bound) < bound);
+    }
+  }
+
+  #[test]
+  fn state_round_trip() {
+    let mut rng = GameRng::new(5);
+    rng.next_u32();
+    let (state, inc) = rng.get_state();
+    let mut copy = GameRng::from_state(state, inc);
+    assert_eq!(rng.next_u64(), copy.next_u64());
+  }
+}
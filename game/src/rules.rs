@@ -0,0 +1,170 @@
+use crate::direction::DirectionSet;
+use crate::event::Cell;
+use crate::merge::MergeRules;
+use crate::spawn::{weights_from_str, SpawnLocation, WeightedSpawn};
+
+pub const DEFAULT_TARGET: i32 = 2048;
+
+/// Everything that makes one game variant different from another
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rules {
+  pub merge_rule: MergeRules,
+  pub directions: DirectionSet,
+  pub spawn: WeightedSpawn,
+  /// The game is won when a tile reaches this number, `None` to play without winning
+  pub target: Option<i32>,
+  /// Cells tiles can't enter or pass, the level of the game. Lines of a push end at them
+  pub obstacles: Vec<Cell>,
+  /// Lines go around the edges of the board, see `Field::push_dir`
+  pub wrap: bool,
+}
+
+impl Default for Rules {
+  fn default() -> Self {
+    Rules {
+      merge_rule: Default::default(),
+      directions: Default::default(),
+      spawn: Default::default(),
+      target: Some(DEFAULT_TARGET),
+      obstacles: Vec::new(),
+      wrap: false,
+    }
+  }
+}
+
+/// Named obstacle layouts for `level=NAME`, made for boards of 4x4 and bigger
+pub const LEVELS: [(&str, &[Cell]); 4] = [
+  ("corner", &[(0, 0)]),
+  ("diagonal", &[(1, 1), (2, 2)]),
+  ("ring", &[(1, 1), (2, 1), (1, 2), (2, 2)]),
+  ("gates", &[(1, 0), (2, 3)]),
+];
+
+pub fn level_obstacles(name: &str) -> Option<Vec<Cell>> {
+  LEVELS.iter().find(|(n, _)| *n == name).map(|(_, cells)| cells.to_vec())
+}
+
+/// Obstacle cells as X:Y separated by commas, "-" for none
+pub fn obstacles_to_string(obstacles: &[Cell]) -> String {
+  if obstacles.is_empty() {
+    "-".to_string()
+  } else {
+    obstacles.iter().map(|(x, y)| format!("{}:{}", x, y)).collect::<Vec<_>>().join(",")
+  }
+}
+
+pub fn obstacles_from_str(text: &str) -> Option<Vec<Cell>> {
+  if text == "-" {
+    return Some(Vec::new());
+  }
+  text.split(',').map(|cell| {
+    let (x, y) = cell.split_once(':')?;
+    Some((x.parse().ok()?, y.parse().ok()?))
+  }).collect()
+}
+
+impl Rules {
+  pub fn is_obstacle(&self, cell: Cell) -> bool {
+    self.obstacles.contains(&cell)
+  }
+
+  /// Checks that the rules fit a board of the size: obstacles on the board, each once, and a free cell left
+  pub fn validate(&self, width: usize, height: usize) -> Result<(), String> {
+    for (i, &(x, y)) in self.obstacles.iter().enumerate() {
+      if x >= width || y >= height {
+        return Err(format!("obstacle {}:{} is off the {}x{} board", x, y, width, height));
+      }
+      if self.obstacles[.. i].contains(&(x, y)) {
+        return Err(format!("obstacle {}:{} is listed twice", x, y));
+      }
+    }
+    if self.obstacles.len() >= width * height {
+      return Err("obstacles leave no free cell".to_string());
+    }
+    Ok(())
+  }
+
+  /// Applies one command line option: a merge rule name, a direction set name,
+  /// `target=N`, `target=none`, `obstacles=X:Y,...`, `level=NAME` from `LEVELS`, `wrap`, `spawn=random|corners|away`,
+  /// `tiles=N` for tiles per move or `weights=NUMBER:WEIGHT,...`.
+  /// `Ok(false)` if the option is not about rules, an error for a bad value
+  pub fn set_option(&mut self, option: &str) -> Result<bool, String> {
+    if let Some(merge_rule) = MergeRules::from_name(option) {
+      self.merge_rule = merge_rule;
+    } else if let Some(directions) = DirectionSet::from_name(option) {
+      self.directions = directions;
+    } else if let Some(target) = option.strip_prefix("target=") {
+      self.target = match target {
+        "none" => None,
+        target => Some(
+          target.parse().ok().filter(|t| *t > 0).ok_or_else(|| format!("bad target {}", target))?
+        ),
+      };
+    } else if let Some(obstacles) = option.strip_prefix("obstacles=") {
+      self.obstacles = obstacles_from_str(obstacles).ok_or_else(|| format!("bad obstacles {}", obstacles))?;
+    } else if let Some(level) = option.strip_prefix("level=") {
+      self.obstacles = level_obstacles(level).ok_or_else(|| format!("unknown level {}", level))?;
+    } else if option == "wrap" {
+      self.wrap = true;
+    } else if let Some(location) = option.strip_prefix("spawn=") {
+      self.spawn.location = SpawnLocation::from_name(location)
+        .ok_or_else(|| format!("unknown spawn location {}", location))?;
+    } else if let Some(count) = option.strip_prefix("tiles=") {
+      let count = count.parse().map_err(|_| format!("bad tile count {}", count))?;
+      self.spawn = WeightedSpawn::new(self.spawn.weights.clone(), count, self.spawn.location)?;
+    } else if let Some(weights) = option.strip_prefix("weights=") {
+      let weights = weights_from_str(weights).ok_or_else(|| format!("bad weights {}", weights))?;
+      self.spawn = WeightedSpawn::new(weights, self.spawn.count, self.spawn.location)?;
+    } else {
+      return Ok(false);
+    }
+    Ok(true)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn options() {
+    let mut rules = Rules::default();
+    assert_eq!(rules.set_option("fibonacci"), Ok(true));
+    assert_eq!(rules.set_option("diagonal"), Ok(true));
+    assert_eq!(rules.set_option("target=none"), Ok(true));
+    assert_eq!(rules.set_option("obstacles=1:2,0:0"), Ok(true));
+    assert_eq!(rules.set_option("wrap"), Ok(true));
+    assert_eq!(rules.set_option("seed=1"), Ok(false));
+    assert_eq!(rules.set_option("diagnoal"), Ok(false));
+    assert!(rules.set_option("target=2O48").is_err());
+    assert!(rules.set_option("target=0").is_err());
+    assert!(rules.set_option("obstacles=1;1").is_err());
+    assert_eq!(rules.set_option("level=ring"), Ok(true));
+    assert_eq!(rules.obstacles.len(), 4);
+    assert!(rules.set_option("level=maze").is_err());
+    assert_eq!(rules.set_option("obstacles=1:2,0:0"), Ok(true));
+    assert_eq!(rules.set_option("spawn=corners"), Ok(true));
+    assert_eq!(rules.set_option("tiles=2"), Ok(true));
+    assert_eq!(rules.set_option("weights=2:3,4:1"), Ok(true));
+    assert!(rules.set_option("spawn=middle").is_err());
+    assert!(rules.set_option("tiles=0").is_err());
+    assert!(rules.set_option("weights=2:0,4:0").is_err());
+    assert!(rules.set_option("weights=2").is_err());
+    assert_eq!(rules.merge_rule, MergeRules::Fibonacci);
+    assert_eq!(rules.directions, DirectionSet::DIAGONAL);
+    assert_eq!(rules.target, None);
+    assert!(rules.wrap);
+    assert_eq!(rules.spawn, WeightedSpawn::new(vec![(2, 3), (4, 1)], 2, SpawnLocation::CornersFirst).unwrap());
+    assert_eq!(rules.obstacles, [(1, 2), (0, 0)]);
+    assert_eq!(obstacles_to_string(&rules.obstacles), "1:2,0:0");
+    assert_eq!(obstacles_from_str("-"), Some(Vec::new()));
+    assert_eq!(obstacles_from_str("1:2,x"), None);
+    assert_eq!(rules.validate(3, 3), Ok(()));
+    assert!(rules.validate(2, 2).is_err());
+    rules.obstacles = vec![(0, 0), (0, 0)];
+    assert!(rules.validate(3, 3).is_err());
+    rules.obstacles = vec![(0, 0)];
+    assert!(rules.validate(1, 1).is_err());
+    assert!(LEVELS.iter().all(|(_, cells)| Rules {obstacles: cells.to_vec(), ..Default::default()}.validate(4, 4).is_ok()));
+  }
+}
@@ -0,0 +1,430 @@
+//! Saved games as text, one item per line, words separated by spaces.
+//! Empty lines and lines starting with `#` are skipped. Version 1:
+//!
+//! ```text
+//! 2048-save 1
+//! size WIDTH HEIGHT
+//! merge pow2sum|classic|fibonacci|threes
+//! directions all|orthogonal|diagonal|DIGITS     numpad digits of the directions, "8426"
+//! target NUMBER|none
+//! spawn COUNT random|corners|away [NUMBER:WEIGHT ...]
+//! obstacles X:Y,...   only for levels with obstacle cells
+//! wrap                only for boards where lines go around the edges
+//! rng VERSION         `rng::RNG_VERSION` of the generator that spawned the tiles
+//! seed SEED
+//! start POSITION      only for games from a custom position, in the notation of `notation`
+//! history LIMIT
+//! played MILLISECONDS ASSISTED    play time, whether a computer player made moves
+//! moves DIGITS|-      moves from the start to the current field, - for none
+//! undone DIGITS|-     moves of the redo fields, the next one last
+//! field current SCORE BEST WON KEEP_GOING GAME_OVER RNG_STATE RNG_INC
+//! HEIGHT rows of WIDTH numbers, 0 for an empty cell
+//! field undo ...      the same for every move that can be undone, the oldest first
+//! field redo ...      every move that can be redone, the next one last
+//! end
+//! ```
+//!
+//! Flags are 0 or 1. A reader refuses versions other than its own
+
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::direction::{Direction, DirectionSet};
+use crate::field::Field;
+use crate::game::{Game, Snapshot};
+use crate::merge::MergeRules;
+use crate::notation::{read_position, write_position, Side};
+use crate::rng::{GameRng, RNG_VERSION};
+use crate::rules::{obstacles_from_str, obstacles_to_string, Rules};
+use crate::spawn::{SpawnLocation, WeightedSpawn};
+
+pub const SAVE_MAGIC: &str = "2048-save";
+pub const SAVE_VERSION: u32 = 1;
+
+pub(crate) fn invalid(message: String) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+pub(crate) fn parse<T: FromStr>(word: &str) -> io::Result<T> {
+  word.parse().map_err(|_| invalid(format!("bad value {}", word)))
+}
+
+fn parse_flag(word: &str) -> io::Result<bool> {
+  match word {
+    "0" => Ok(false),
+    "1" => Ok(true),
+    _ => Err(invalid(format!("bad flag {}", word))),
+  }
+}
+
+/// Direction set by name, numpad digits for sets without one
+pub fn directions_to_string(directions: DirectionSet) -> String {
+  match directions.get_name() {
+    Some(name) => name.to_string(),
+    None => directions.iter().map(|d| d.get_digit()).collect(),
+  }
+}
+
+pub fn directions_from_str(text: &str) -> Option<DirectionSet> {
+  DirectionSet::from_name(text).or_else(|| text.chars().map(Direction::from_digit).collect())
+}
+
+/// Moves as numpad digits, "-" for no moves
+pub fn moves_to_string(moves: &[Direction]) -> String {
+  if moves.is_empty() {
+    "-".to_string()
+  } else {
+    moves.iter().map(|d| d.get_digit()).collect()
+  }
+}
+
+pub fn moves_from_str(text: &str) -> Option<Vec<Direction>> {
+  if text == "-" {
+    Some(Vec::new())
+  } else {
+    text.chars().map(Direction::from_digit).collect()
+  }
+}
+
+pub(crate) fn parse_moves(text: &str) -> io::Result<Vec<Direction>> {
+  moves_from_str(text).ok_or_else(|| invalid(format!("bad moves {}", text)))
+}
+
+fn write_field(text: &mut String, kind: &str, field: &Field, rng: &GameRng) {
+  let (state, inc) = rng.get_state();
+  writeln!(
+    text, "field {} {} {} {} {} {} {} {}",
+    kind, field.get_score(), field.get_best_score(), field.is_won() as u8,
+    field.is_keep_going() as u8, field.is_game_over() as u8, state, inc
+  ).unwrap();
+  for line in field.get_numbers() {
+    let numbers: Vec<String> = line.iter().map(|n| n.to_string()).collect();
+    writeln!(text, "{}", numbers.join(" ")).unwrap();
+  }
+}
+
+/// What saves and replays start with
+pub(crate) struct Header {
+  pub(crate) size: (usize, usize),
+  pub(crate) rules: Rules,
+  pub(crate) seed: u64,
+  pub(crate) start: Option<Field>,
+}
+
+/// Writes the lines from `size` to `start`, the same in saves and replays
+pub(crate) fn write_header(
+  text: &mut String,
+  size: (usize, usize),
+  rules: &Rules,
+  seed: u64,
+  start: Option<&Field>
+) {
+  writeln!(text, "size {} {}", size.0, size.1).unwrap();
+  writeln!(text, "merge {}", rules.merge_rule.get_name()).unwrap();
+  writeln!(text, "directions {}", directions_to_string(rules.directions)).unwrap();
+  match rules.target {
+    Some(target) => writeln!(text, "target {}", target).unwrap(),
+    None => writeln!(text, "target none").unwrap(),
+  }
+  write!(text, "spawn {} {}", rules.spawn.count, rules.spawn.location.get_name()).unwrap();
+  for (number, weight) in &rules.spawn.weights {
+    write!(text, " {}:{}", number, weight).unwrap();
+  }
+  writeln!(text).unwrap();
+  if !rules.obstacles.is_empty() {
+    writeln!(text, "obstacles {}", obstacles_to_string(&rules.obstacles)).unwrap();
+  }
+  if rules.wrap {
+    writeln!(text, "wrap").unwrap();
+  }
+  writeln!(text, "rng {}", RNG_VERSION).unwrap();
+  writeln!(text, "seed {}", seed).unwrap();
+  if let Some(start) = start {
+    writeln!(text, "start {}", write_position(start, Side::Player)).unwrap();
+  }
+}
+
+pub fn write_game(game: &Game) -> String {
+  let mut text = String::new();
+  writeln!(text, "{} {}", SAVE_MAGIC, SAVE_VERSION).unwrap();
+  let field = &game.field;
+  write_header(&mut text, field.get_size(), field.get_rules(), game.seed, game.start.as_ref());
+  writeln!(text, "history {}", game.history_limit).unwrap();
+  writeln!(text, "played {} {}", game.play_time.as_millis(), game.assisted as u8).unwrap();
+  writeln!(text, "moves {}", moves_to_string(&game.moves)).unwrap();
+  writeln!(text, "undone {}", moves_to_string(&game.undone_moves)).unwrap();
+
+  write_field(&mut text, "current", &game.field, &game.rng);
+  for snapshot in &game.undo {
+    write_field(&mut text, "undo", &snapshot.field, &snapshot.rng);
+  }
+  for snapshot in &game.redo {
+    write_field(&mut text, "redo", &snapshot.field, &snapshot.rng);
+  }
+  writeln!(text, "end").unwrap();
+  text
+}
+
+/// Words of the lines with content, with line numbers for error messages
+pub(crate) struct Lines<'t> {
+  lines: Vec<(usize, Vec<&'t str>)>,
+  next: usize,
+}
+
+impl<'t> Lines<'t> {
+  pub(crate) fn new(text: &'t str) -> Self {
+    let lines = text.lines().map(str::trim).enumerate()
+      .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+      .map(|(i, line)| (i + 1, line.split_whitespace().collect()))
+      .collect();
+    Lines {lines, next: 0}
+  }
+
+  fn next(&mut self) -> io::Result<&[&'t str]> {
+    self.next += 1;
+    match self.lines.get(self.next - 1) {
+      Some((_, words)) => Ok(words),
+      None => Err(invalid("unexpected end of the save".to_string())),
+    }
+  }
+
+  /// Words after `key` on the next line, exactly `count` of them unless it is `None`
+  pub(crate) fn expect(&mut self, key: &str, count: Option<usize>) -> io::Result<Vec<&'t str>> {
+    let words = self.next()?.to_vec();
+    if words[0] != key || count.is_some_and(|c| words.len() != c + 1) {
+      let number = self.lines[self.next - 1].0;
+      return Err(invalid(format!("line {}: expected {}", number, key)));
+    }
+    Ok(words[1 ..].to_vec())
+  }
+
+  pub(crate) fn peek_key(&self) -> Option<&'t str> {
+    self.lines.get(self.next).map(|(_, words)| words[0])
+  }
+}
+
+/// Reads what `write_header` wrote
+pub(crate) fn read_header(lines: &mut Lines) -> io::Result<Header> {
+  let size = lines.expect("size", Some(2))?;
+  let size: (usize, usize) = (parse(size[0])?, parse(size[1])?);
+  if size.0 == 0 || size.1 == 0 {
+    return Err(invalid("empty field".to_string()));
+  }
+
+  let mut rules = Rules::default();
+  let merge = lines.expect("merge", Some(1))?;
+  rules.merge_rule = MergeRules::from_name(merge[0])
+    .ok_or_else(|| invalid(format!("unknown merge rule {}", merge[0])))?;
+  let directions = lines.expect("directions", Some(1))?;
+  rules.directions = directions_from_str(directions[0])
+    .ok_or_else(|| invalid(format!("bad directions {}", directions[0])))?;
+  let target = lines.expect("target", Some(1))?;
+  rules.target = match target[0] {
+    "none" => None,
+    target => match parse(target)? {
+      target if target > 0 => Some(target),
+      _ => return Err(invalid(format!("bad target {}", target))),
+    },
+  };
+
+  let spawn = lines.expect("spawn", None)?;
+  if spawn.len() < 2 {
+    return Err(invalid("bad spawn".to_string()));
+  }
+  let location = SpawnLocation::from_name(spawn[1])
+    .ok_or_else(|| invalid(format!("unknown spawn location {}", spawn[1])))?;
+  let mut weights = Vec::new();
+  for weight in &spawn[2 ..] {
+    let (number, weight) = weight.split_once(':')
+      .ok_or_else(|| invalid(format!("bad spawn weight {}", weight)))?;
+    weights.push((parse(number)?, parse(weight)?));
+  }
+  rules.spawn = WeightedSpawn::new(weights, parse(spawn[0])?, location).map_err(invalid)?;
+
+  if lines.peek_key() == Some("obstacles") {
+    let obstacles = lines.expect("obstacles", Some(1))?;
+    rules.obstacles = obstacles_from_str(obstacles[0])
+      .ok_or_else(|| invalid(format!("bad obstacles {}", obstacles[0])))?;
+  }
+  if lines.peek_key() == Some("wrap") {
+    lines.expect("wrap", Some(0))?;
+    rules.wrap = true;
+  }
+  rules.validate(size.0, size.1).map_err(invalid)?;
+
+  let rng_version: u32 = parse(lines.expect("rng", Some(1))?[0])?;
+  if rng_version != RNG_VERSION {
+    return Err(invalid(format!("spawns of random generator version {} can't be reproduced", rng_version)));
+  }
+  let seed = parse(lines.expect("seed", Some(1))?[0])?;
+  let mut start = None;
+  if lines.peek_key() == Some("start") {
+    let position = lines.expect("start", Some(4))?.join(" ");
+    let (mut field, _) = read_position(&position).map_err(invalid)?;
+    if field.get_size() != size {
+      return Err(invalid("the start doesn't match the size".to_string()));
+    }
+    field.try_set_rules(rules.clone()).map_err(invalid)?;
+    start = Some(field);
+  }
+  Ok(Header {size, rules, seed, start})
+}
+
+/// The kind of the field: current, undo or redo, and the field with its generator
+fn read_field<'t>(
+  lines: &mut Lines<'t>,
+  size: (usize, usize),
+  rules: &Rules
+) -> io::Result<(&'t str, Snapshot)> {
+  let state = lines.expect("field", Some(8))?;
+
+  let mut numbers = Vec::new();
+  for _ in 0 .. size.1 {
+    let line = lines.next()?;
+    if line.len() != size.0 {
+      return Err(invalid(format!("expected {} numbers in a row", size.0)));
+    }
+    let row = line.iter().map(|n| parse(n)).collect::<io::Result<Vec<i32>>>()?;
+    if let Some(n) = row.iter().find(|n| **n < 0) {
+      return Err(invalid(format!("bad number {}", n)));
+    }
+    numbers.push(row);
+  }
+
+  let mut field = Field::from_numbers(numbers);
+  field.try_set_rules(rules.clone()).map_err(invalid)?;
+  field.score = parse(state[1])?;
+  field.best_score = parse(state[2])?;
+  field.won = parse_flag(state[3])?;
+  field.keep_going = parse_flag(state[4])?;
+  field.game_over = parse_flag(state[5])?;
+  let rng = GameRng::from_state(parse(state[6])?, parse(state[7])?);
+  Ok((state[0], Snapshot {field, rng}))
+}
+
+pub fn read_game(text: &str) -> io::Result<Game> {
+  let mut lines = Lines::new(text);
+  let header = lines.expect(SAVE_MAGIC, Some(1))?;
+  let version: u32 = parse(header[0])?;
+  if version != SAVE_VERSION {
+    return Err(invalid(format!("unsupported save version {}", version)));
+  }
+
+  let Header {size, rules, seed, start} = read_header(&mut lines)?;
+  let history_limit = parse(lines.expect("history", Some(1))?[0])?;
+  let played = lines.expect("played", Some(2))?;
+  let play_time = Duration::from_millis(parse(played[0])?);
+  let assisted = parse_flag(played[1])?;
+  let moves = parse_moves(lines.expect("moves", Some(1))?[0])?;
+  let undone_moves = parse_moves(lines.expect("undone", Some(1))?[0])?;
+
+  let (kind, current) = read_field(&mut lines, size, &rules)?;
+  if kind != "current" {
+    return Err(invalid("expected the current field".to_string()));
+  }
+  let mut game = Game::new(size.0, size.1, rules.clone(), seed);
+  game.field = current.field;
+  game.rng = current.rng;
+  game.history_limit = history_limit;
+  game.play_time = play_time;
+  game.assisted = assisted;
+  game.moves = moves;
+  game.undone_moves = undone_moves;
+  game.start = start;
+  while lines.peek_key() == Some("field") {
+    match read_field(&mut lines, size, &rules)? {
+      ("undo", snapshot) if game.redo.is_empty() => game.undo.push_back(snapshot),
+      ("redo", snapshot) => game.redo.push(snapshot),
+      (kind, _) => return Err(invalid(format!("unexpected {} field", kind))),
+    }
+  }
+  lines.expect("end", Some(0))?;
+  if game.undone_moves.len() != game.redo.len() {
+    return Err(invalid("undone moves don't match redo fields".to_string()));
+  }
+  Ok(game)
+}
+
+pub fn save_game<P: AsRef<Path>>(game: &Game, path: P) -> io::Result<()> {
+  fs::write(path, write_game(game))
+}
+
+pub fn load_game<P: AsRef<Path>>(path: P) -> io::Result<Game> {
+  read_game(&fs::read_to_string(path)?)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::game::play_in_turn;
+
+  fn played_game() -> Game {
+    let rules = Rules {
+      directions: [Direction::Up, Direction::Left, Direction::DownRight].iter().copied().collect(),
+      spawn: WeightedSpawn {location: SpawnLocation::AwayFromMove, ..WeightedSpawn::classic()},
+      target: None,
+      ..Default::default()
+    };
+    let mut game = Game::new(5, 3, rules, 77);
+    play_in_turn(&mut game, 30, |_, _| {});
+    game.undo();
+    game.undo();
+    game.add_play_time(Duration::from_millis(61500));
+    game.set_assisted();
+    game
+  }
+
+  #[test]
+  fn round_trip() {
+    let game = played_game();
+    let text = write_game(&game);
+    assert!(text.starts_with("2048-save 1\nsize 5 3\nmerge pow2sum\ndirections 843\ntarget none\n"));
+    assert!(text.contains("\nhistory 100\nplayed 61500 1\n"));
+    assert_eq!(read_game(&text).unwrap(), game);
+
+    let mut loaded = read_game(&text).unwrap();
+    let mut original = game;
+    assert!(loaded.redo() && original.redo());
+    assert_eq!(loaded, original);
+  }
+
+  #[test]
+  fn comments_and_errors() {
+    let text = write_game(&Game::new(4, 4, Rules::default(), 1));
+    let commented = format!("# saved by a test\n\n{}", text);
+    assert_eq!(read_game(&commented).unwrap(), Game::new(4, 4, Rules::default(), 1));
+
+    assert!(read_game(&text.replace("2048-save 1", "2048-save 2")).is_err());
+    assert!(read_game(&text.replace("2048-save 1", "2048-save one")).is_err());
+    assert!(read_game(&text.replace("\nrng 1\n", "\nrng 2\n")).is_err());
+    assert!(read_game(&text.replace("\nrng 1\n", "\n")).is_err());
+    assert!(read_game(&text.replace("merge pow2sum", "merge chess")).is_err());
+    assert!(read_game(&text.replace("target 2048", "target 0")).is_err());
+    assert!(read_game(&text.replace("target 2048", "target -8")).is_err());
+    assert!(read_game(&text.replacen("\n0 ", "\n-2 ", 1)).is_err());
+    assert!(read_game(&text.replace("\nend\n", "\n")).is_err());
+    assert!(read_game(&text[.. text.len() / 2]).is_err());
+  }
+
+  #[test]
+  fn obstacles() {
+    let rules = Rules {obstacles: vec![(1, 1), (2, 0)], wrap: true, ..Default::default()};
+    let mut game = Game::new(4, 3, rules, 5);
+    for dir in Direction::ALL.iter().cycle().take(10) {
+      game.make_move(*dir);
+    }
+    let text = write_game(&game);
+    assert!(text.contains("\nobstacles 1:1,2:0\nwrap\nrng 1\nseed 5\n"));
+    assert_eq!(read_game(&text).unwrap(), game);
+    assert!(read_game(&text.replace("obstacles 1:1,2:0", "obstacles 1:1,4:0")).is_err());
+
+    let (field, side) = read_position("3x2 2#./... p 0").unwrap();
+    let text = write_game(&crate::notation::start_game(field, side, 1));
+    assert!(text.contains("\nobstacles 1:0\n"));
+    assert!(read_game(&text.replace("obstacles 1:0", "obstacles 0:0")).is_err());
+  }
+}
@@ -0,0 +1,252 @@
+//! Table of the best finished games, kept as text:
+//!
+//! ```text
+//! 2048-scores 1
+//! entry SCORE MAX_TILE MOVES SECONDS DATE SEED VARIANT
+//! ```
+//!
+//! One `entry` per game, the best first. DATE is in seconds since 1970-01-01 UTC,
+//! VARIANT is written by `variant_name`
+
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::field::Field;
+use crate::game::Game;
+use crate::rules::obstacles_to_string;
+use crate::save::{directions_to_string, invalid, parse, Lines};
+use crate::spawn::SpawnLocation;
+
+pub const SCORES_MAGIC: &str = "2048-scores";
+pub const SCORES_VERSION: u32 = 1;
+pub const DEFAULT_TABLE_SIZE: usize = 10;
+
+/// Size and rules in one word: `4x4/pow2sum/all/2048`, with `/X:Y,...` for obstacles,
+/// `/wrap` for boards without edges and the spawn settings that are not the default
+/// as their command line options: `/tiles=N`, `/spawn=LOCATION`, `/weights=NUMBER:WEIGHT,...`
+pub fn variant_name(field: &Field) -> String {
+  let (width, height) = field.get_size();
+  let rules = field.get_rules();
+  let target = rules.target.map_or("none".to_string(), |t| t.to_string());
+  let mut name = format!(
+    "{}x{}/{}/{}/{}",
+    width, height, rules.merge_rule.get_name(), directions_to_string(rules.directions), target
+  );
+  if !rules.obstacles.is_empty() {
+    name.push('/');
+    name.push_str(&obstacles_to_string(&rules.obstacles));
+  }
+  if rules.wrap {
+    name.push_str("/wrap");
+  }
+  let spawn = &rules.spawn;
+  if spawn.count != 1 {
+    write!(name, "/tiles={}", spawn.count).unwrap();
+  }
+  if spawn.location != SpawnLocation::Random {
+    write!(name, "/spawn={}", spawn.location.get_name()).unwrap();
+  }
+  if !spawn.weights.is_empty() {
+    let weights: Vec<String> = spawn.weights.iter().map(|(n, w)| format!("{}:{}", n, w)).collect();
+    write!(name, "/weights={}", weights.join(",")).unwrap();
+  }
+  name
+}
+
+/// Date as YYYY-MM-DD from seconds since 1970-01-01 UTC
+pub fn format_date(seconds: u64) -> String {
+  // Days to the civil date, see http://howardhinnant.github.io/date_algorithms.html
+  let days = (seconds / 86400) as i64 + 719468;
+  let era = days / 146097;
+  let day_of_era = days - era * 146097;
+  let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+  let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+  let month_index = (5 * day_of_year + 2) / 153;
+  let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+  let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+  let year = year_of_era + era * 400 + (month <= 2) as i64;
+  format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Duration as M:SS or H:MM:SS
+pub fn format_duration(seconds: u64) -> String {
+  if seconds >= 3600 {
+    format!("{}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+  } else {
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScoreEntry {
+  pub score: u64,
+  pub max_number: i32,
+  pub moves: usize,
+  /// Play time in seconds
+  pub duration: u64,
+  /// Seconds since 1970-01-01 UTC
+  pub date: u64,
+  pub seed: u64,
+  pub variant: String,
+}
+
+impl ScoreEntry {
+  pub fn from_game(game: &Game, date: u64) -> Self {
+    let field = game.get_field();
+    ScoreEntry {
+      score: field.get_score(),
+      max_number: field.get_numbers().iter().flatten().copied().max().unwrap_or(0),
+      moves: game.get_moves().len(),
+      duration: game.get_play_time().as_secs(),
+      date,
+      seed: game.get_seed(),
+      variant: variant_name(field),
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HighScores {
+  entries: Vec<ScoreEntry>,
+  capacity: usize,
+}
+
+impl Default for HighScores {
+  fn default() -> Self {
+    HighScores::new(DEFAULT_TABLE_SIZE)
+  }
+}
+
+impl HighScores {
+  pub fn new(capacity: usize) -> Self {
+    HighScores {entries: Vec::new(), capacity}
+  }
+
+  /// The best first
+  pub fn get_entries(&self) -> &[ScoreEntry] {
+    &self.entries
+  }
+
+  /// Place of the entry counting from 0, `None` if it is not good enough for the table.
+  /// Of equal scores the older entry stays higher
+  pub fn add(&mut self, entry: ScoreEntry) -> Option<usize> {
+    let rank = self.entries.iter().position(|e| e.score < entry.score).unwrap_or(self.entries.len());
+    if rank >= self.capacity {
+      return None;
+    }
+    self.entries.insert(rank, entry);
+    self.entries.truncate(self.capacity);
+    Some(rank)
+  }
+
+  pub fn to_text(&self) -> String {
+    let mut text = String::new();
+    writeln!(text, "{} {}", SCORES_MAGIC, SCORES_VERSION).unwrap();
+    for e in &self.entries {
+      writeln!(
+        text, "entry {} {} {} {} {} {} {}",
+        e.score, e.max_number, e.moves, e.duration, e.date, e.seed, e.variant
+      ).unwrap();
+    }
+    text
+  }
+
+  /// Entries that do not fit into `capacity` are dropped
+  pub fn from_text(text: &str, capacity: usize) -> io::Result<Self> {
+    let mut lines = Lines::new(text);
+    let header = lines.expect(SCORES_MAGIC, Some(1))?;
+    if header[0] != SCORES_VERSION.to_string() {
+      return Err(invalid(format!("unsupported high scores version {}", header[0])));
+    }
+
+    let mut result = HighScores::new(capacity);
+    while lines.peek_key().is_some() {
+      let e = lines.expect("entry", Some(7))?;
+      result.add(ScoreEntry {
+        score: parse(e[0])?,
+        max_number: parse(e[1])?,
+        moves: parse(e[2])?,
+        duration: parse(e[3])?,
+        date: parse(e[4])?,
+        seed: parse(e[5])?,
+        variant: e[6].to_string(),
+      });
+    }
+    Ok(result)
+  }
+
+  pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+    fs::write(path, self.to_text())
+  }
+
+  /// An empty table if there is no file yet
+  pub fn load<P: AsRef<Path>>(path: P, capacity: usize) -> io::Result<Self> {
+    match fs::read_to_string(path) {
+      Ok(text) => Self::from_text(&text, capacity),
+      Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(HighScores::new(capacity)),
+      Err(e) => Err(e),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::rules::Rules;
+
+  fn entry(score: u64) -> ScoreEntry {
+    ScoreEntry {
+      score,
+      max_number: 256,
+      moves: 100,
+      duration: 75,
+      date: 1_700_000_000,
+      seed: score,
+      variant: "4x4/pow2sum/all/2048".to_string(),
+    }
+  }
+
+  #[test]
+  fn keeps_the_best() {
+    let mut scores = HighScores::new(3);
+    assert_eq!(scores.add(entry(100)), Some(0));
+    assert_eq!(scores.add(entry(300)), Some(0));
+    assert_eq!(scores.add(entry(100)), Some(2));
+    assert_eq!(scores.add(entry(50)), None);
+    assert_eq!(scores.add(entry(200)), Some(1));
+    let scores: Vec<u64> = scores.get_entries().iter().map(|e| e.score).collect();
+    assert_eq!(scores, [300, 200, 100]);
+  }
+
+  #[test]
+  fn text_round_trip() {
+    let mut scores = HighScores::default();
+    scores.add(entry(500));
+    scores.add(entry(700));
+    let text = scores.to_text();
+    assert_eq!(HighScores::from_text(&text, DEFAULT_TABLE_SIZE).unwrap(), scores);
+    assert_eq!(HighScores::from_text(&text, 1).unwrap().get_entries().len(), 1);
+    assert!(HighScores::from_text(&text.replace("entry 500", "entry x"), 10).is_err());
+    assert!(HighScores::from_text(&text.replace("entry 500 256", "entry 500 4294967296"), 10).is_err());
+  }
+
+  #[test]
+  fn names_and_dates() {
+    let game = Game::new(5, 4, Rules {target: None, ..Default::default()}, 1);
+    let entry = ScoreEntry::from_game(&game, 0);
+    assert_eq!(entry.variant, "5x4/pow2sum/all/none");
+    let mut rules = Rules::default();
+    for option in ["tiles=3", "spawn=corners", "weights=2:9,4:1"] {
+      rules.set_option(option).unwrap();
+    }
+    let entry = ScoreEntry::from_game(&Game::new(4, 4, rules, 1), 0);
+    assert_eq!(entry.variant, "4x4/pow2sum/all/2048/tiles=3/spawn=corners/weights=2:9,4:1");
+    assert_eq!(format_date(0), "1970-01-01");
+    assert_eq!(format_date(1_700_000_000), "2023-11-14");
+    assert_eq!(format_date(951_782_400), "2000-02-29");
+    assert_eq!(format_duration(75), "1:15");
+    assert_eq!(format_duration(3725), "1:02:05");
+  }
+}
@@ -0,0 +1,266 @@
+use rand::RngCore;
+
+use crate::direction::Direction;
+use crate::field::Field;
+use crate::merge::MergeRule;
+use crate::rng::gen_below;
+
+/// A tile to add: (x, y, number)
+pub type SpawnedItem = (usize, usize, i32);
+
+/// Decides where new tiles appear and which numbers they carry
+pub trait SpawnPolicy {
+  /// Chooses tiles to add to `field`, `last_move` is `None` for the first tiles of a game.
+  /// Only empty cells must be returned, each at most once
+  fn spawn<R: RngCore + ?Sized>(
+    &self,
+    field: &Field,
+    last_move: Option<Direction>,
+    rng: &mut R
+  ) -> Vec<SpawnedItem>;
+}
+
+/// Where new tiles are put
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SpawnLocation {
+  /// Any empty cell, equally likely
+  #[default]
+  Random,
+  /// Empty corners if there are any, otherwise any empty cell
+  CornersFirst,
+  /// Empty cells on the side opposite to the last move
+  AwayFromMove,
+}
+
+impl SpawnLocation {
+  pub const ALL: [SpawnLocation; 3] =
+    [SpawnLocation::Random, SpawnLocation::CornersFirst, SpawnLocation::AwayFromMove];
+
+  pub fn get_name(self) -> &'static str {
+    match self {
+      SpawnLocation::Random => "random",
+      SpawnLocation::CornersFirst => "corners",
+      SpawnLocation::AwayFromMove => "away",
+    }
+  }
+
+  pub fn from_name(name: &str) -> Option<Self> {
+    Self::ALL.iter().copied().find(|l| l.get_name() == name)
+  }
+}
+
+/// Spawn policy described by data: number weights, tiles per move and location strategy
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WeightedSpawn {
+  /// (number, weight) pairs, empty means the start numbers of the merge rule, equally likely
+  pub weights: Vec<(i32, u32)>,
+  /// Tiles added after every move
+  pub count: usize,
+  pub location: SpawnLocation,
+}
+
+impl Default for WeightedSpawn {
+  fn default() -> Self {
+    WeightedSpawn {weights: Vec::new(), count: 1, location: SpawnLocation::Random}
+  }
+}
+
+/// Number weights as NUMBER:WEIGHT separated by commas, "-" for the start numbers of the merge rule
+pub fn weights_from_str(text: &str) -> Option<Vec<(i32, u32)>> {
+  if text == "-" {
+    return Some(Vec::new());
+  }
+  text.split(',').map(|weight| {
+    let (n, w) = weight.split_once(':')?;
+    Some((n.parse().ok()?, w.parse().ok()?))
+  }).collect()
+}
+
+impl WeightedSpawn {
+  /// Checks that every move adds a tile and that the weights give a distribution
+  /// whose total fits the `u32` the generator draws from
+  pub fn new(weights: Vec<(i32, u32)>, count: usize, location: SpawnLocation) -> Result<Self, String> {
+    if count == 0 {
+      return Err("at least one tile must spawn".to_string());
+    }
+    if weights.iter().any(|(n, _)| *n <= 0) {
+      return Err("spawned numbers must be above zero".to_string());
+    }
+    let spawn = WeightedSpawn {weights, count, location};
+    if !spawn.weights.is_empty() && spawn.get_total_weight() == 0 {
+      return Err("spawn weights add up to zero".to_string());
+    }
+    if spawn.get_total_weight() > u32::MAX as u64 {
+      return Err(format!("spawn weights add up to more than {}", u32::MAX));
+    }
+    Ok(spawn)
+  }
+
+  /// Sum of the weights, in `u64` so that it can't overflow
+  pub fn get_total_weight(&self) -> u64 {
+    self.weights.iter().map(|(_, w)| *w as u64).sum()
+  }
+
+  /// The original 2048 split: 2 with 90% probability, 4 with 10%
+  pub fn classic() -> Self {
+    WeightedSpawn {weights: vec![(2, 9), (4, 1)], ..Default::default()}
+  }
+
+  /// Numbers with their probabilities. Weights that add up to zero, which `new` refuses,
+  /// count as no weights
+  pub fn get_distribution(&self, field: &Field) -> Vec<(i32, f64)> {
+    let total = self.get_total_weight();
+    if total == 0 {
+      let numbers = field.get_rules().merge_rule.start_numbers();
+      numbers.iter().map(|n| (*n, 1.0 / numbers.len() as f64)).collect()
+    } else {
+      self.weights.iter().map(|(n, w)| (*n, *w as f64 / total as f64)).collect()
+    }
+  }
+
+  /// Tiles for a board of `size` with the `empty` cells, the same as `spawn` on a field with them.
+  /// `start_numbers` of the merge rule stand in for missing weights
+  pub fn place<R: RngCore + ?Sized>(
+    &self,
+    size: (usize, usize),
+    mut empty: Vec<(usize, usize)>,
+    start_numbers: &[i32],
+    last_move: Option<Direction>,
+    rng: &mut R
+  ) -> Vec<SpawnedItem> {
+    let mut result = Vec::new();
+    while result.len() < self.count && !empty.is_empty() {
+      let candidates = self.candidates(size, &empty, last_move);
+      let (x, y) = candidates[gen_below(rng, candidates.len())];
+      empty.retain(|c| *c != (x, y));
+      result.push((x, y, self.choose_number(start_numbers, rng)));
+    }
+
+    result
+  }
+
+  /// Weights over `u32::MAX` in total, which `new` refuses, panic in `gen_below`
+  fn choose_number<R: RngCore + ?Sized>(&self, start_numbers: &[i32], rng: &mut R) -> i32 {
+    let total = self.get_total_weight();
+    if total == 0 {
+      return start_numbers[gen_below(rng, start_numbers.len())];
+    }
+
+    let mut r = gen_below(rng, total as usize) as u32;
+    for (n, w) in &self.weights {
+      if r < *w {
+        return *n;
+      }
+      r -= w;
+    }
+
+    unreachable!()
+  }
+
+  /// Cells of `empty` where the next tile may appear, each equally likely
+  pub fn candidates(
+    &self,
+    (width, height): (usize, usize),
+    empty: &[(usize, usize)],
+    last_move: Option<Direction>
+  ) -> Vec<(usize, usize)> {
+    let preferred: Vec<(usize, usize)> = match (self.location, last_move) {
+      (SpawnLocation::CornersFirst, _) => empty.iter().copied().filter(
+        |(x, y)| (*x == 0 || *x == width - 1) && (*y == 0 || *y == height - 1)
+      ).collect(),
+      (SpawnLocation::AwayFromMove, Some(dir)) => {
+        let (dx, dy) = dir.get_delta();
+        let projection = |(x, y): (usize, usize)| x as i32 * dx + y as i32 * dy;
+        let far = empty.iter().map(|c| projection(*c)).min().unwrap_or(0);
+        empty.iter().copied().filter(|c| projection(*c) == far).collect()
+      }
+      _ => Vec::new(),
+    };
+
+    if preferred.is_empty() {
+      empty.to_vec()
+    } else {
+      preferred
+    }
+  }
+}
+
+impl SpawnPolicy for WeightedSpawn {
+  fn spawn<R: RngCore + ?Sized>(
+    &self,
+    field: &Field,
+    last_move: Option<Direction>,
+    rng: &mut R
+  ) -> Vec<SpawnedItem> {
+    let start_numbers = field.get_rules().merge_rule.start_numbers();
+    self.place(field.get_size(), field.get_empty_cells(), start_numbers, last_move, rng)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::rng::GameRng;
+
+  #[test]
+  fn classic_distribution() {
+    let field = Field::new();
+    let distribution = WeightedSpawn::classic().get_distribution(&field);
+    assert_eq!(distribution, vec![(2, 0.9), (4, 0.1)]);
+    assert_eq!(WeightedSpawn::default().get_distribution(&field), vec![(2, 0.5), (4, 0.5)]);
+    let zero = WeightedSpawn {weights: vec![(8, 0)], ..Default::default()};
+    assert_eq!(zero.get_distribution(&field), vec![(2, 0.5), (4, 0.5)]);
+  }
+
+  #[test]
+  fn checked_construction() {
+    assert!(WeightedSpawn::new(vec![(2, 9), (4, 1)], 2, SpawnLocation::CornersFirst).is_ok());
+    assert!(WeightedSpawn::new(Vec::new(), 1, SpawnLocation::Random).is_ok());
+    assert!(WeightedSpawn::new(vec![(2, 0), (4, 0)], 1, SpawnLocation::Random).is_err());
+    assert!(WeightedSpawn::new(vec![(-2, 1)], 1, SpawnLocation::Random).is_err());
+    assert!(WeightedSpawn::new(Vec::new(), 0, SpawnLocation::Random).is_err());
+    assert!(WeightedSpawn::new(vec![(2, 4000000000), (4, 4000000000)], 1, SpawnLocation::Random).is_err());
+    assert!(WeightedSpawn::new(vec![(2, u32::MAX - 1), (4, 1)], 1, SpawnLocation::Random).is_ok());
+    let huge = WeightedSpawn {weights: vec![(2, u32::MAX), (4, u32::MAX)], ..Default::default()};
+    assert_eq!(huge.get_distribution(&Field::new()), vec![(2, 0.5), (4, 0.5)]);
+    assert_eq!(weights_from_str("2:9,4:1"), Some(vec![(2, 9), (4, 1)]));
+    assert_eq!(weights_from_str("2:-1"), None);
+  }
+
+  #[test]
+  fn several_items_per_move() {
+    let mut rng = GameRng::new(7);
+    let field = Field::from_numbers(vec![vec![2, 0, 0], vec![0, 0, 0]]);
+    let policy = WeightedSpawn {weights: vec![(8, 1)], count: 3, ..Default::default()};
+    let items = policy.spawn(&field, None, &mut rng);
+    assert_eq!(items.len(), 3);
+    assert!(items.iter().all(|(x, y, n)| *n == 8 && (*x, *y) != (0, 0)));
+
+    let policy = WeightedSpawn {count: 10, ..Default::default()};
+    assert_eq!(policy.spawn(&field, None, &mut rng).len(), 5);
+  }
+
+  #[test]
+  fn corners_first() {
+    let mut rng = GameRng::new(7);
+    let field = Field::from_numbers(vec![vec![2, 0, 2], vec![0, 0, 0], vec![2, 0, 0]]);
+    let policy = WeightedSpawn {location: SpawnLocation::CornersFirst, ..Default::default()};
+    for _ in 0 .. 10 {
+      let items = policy.spawn(&field, None, &mut rng);
+      assert_eq!((items[0].0, items[0].1), (2, 2));
+    }
+  }
+
+  #[test]
+  fn away_from_move() {
+    let mut rng = GameRng::new(7);
+    let field = Field::from_numbers(vec![vec![0, 0, 0], vec![0, 0, 0], vec![0, 0, 0]]);
+    let policy = WeightedSpawn {location: SpawnLocation::AwayFromMove, ..Default::default()};
+    for _ in 0 .. 10 {
+      let items = policy.spawn(&field, Some(Direction::Right), &mut rng);
+      assert_eq!(items[0].0, 0);
+      let items = policy.spawn(&field, Some(Direction::UpLeft), &mut rng);
+      assert_eq!((items[0].0, items[0].1), (2, 2));
+    }
+  }
+}
@@ -0,0 +1,171 @@
+//! Totals over all games of a player, kept as text:
+//!
+//! ```text
+//! 2048-statistics 1
+//! games PLAYED WON MOVES      MOVES of the finished games together
+//! merges COUNT
+//! directions COUNT x 8        moves to every direction in the numpad order 7 8 9 4 6 1 2 3
+//! tile NUMBER GAMES           games that ended with NUMBER as the best tile, one line per number
+//! end
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::direction::Direction;
+use crate::event::MoveEvent;
+use crate::game::Game;
+use crate::save::{invalid, parse, Lines};
+
+pub const STATISTICS_MAGIC: &str = "2048-statistics";
+pub const STATISTICS_VERSION: u32 = 1;
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Statistics {
+  pub games_played: u64,
+  pub games_won: u64,
+  /// Moves of the finished games, for the average length
+  pub finished_moves: u64,
+  pub merges: u64,
+  /// Moves made to every direction, by `Direction::get_index`. Undone moves stay counted
+  pub direction_moves: [u64; 8],
+  /// Number of games for every best tile they ended with
+  pub best_tiles: BTreeMap<i32, u64>,
+}
+
+impl Statistics {
+  /// Counts a move with the events it returned, moves that did nothing are skipped
+  pub fn record_move(&mut self, dir: Direction, events: &[MoveEvent]) {
+    if events.is_empty() {
+      return;
+    }
+    self.direction_moves[dir.get_index()] += 1;
+    self.merges += events.iter().filter(|e| matches!(e, MoveEvent::Merged {..})).count() as u64;
+  }
+
+  /// Counts a finished game
+  pub fn record_game(&mut self, game: &Game) {
+    let field = game.get_field();
+    self.games_played += 1;
+    if field.is_won() {
+      self.games_won += 1;
+    }
+    self.finished_moves += game.get_moves().len() as u64;
+    let max_number = field.get_numbers().iter().flatten().copied().max().unwrap_or(0);
+    *self.best_tiles.entry(max_number).or_insert(0) += 1;
+  }
+
+  /// Percent of the games won
+  pub fn win_rate(&self) -> f64 {
+    if self.games_played == 0 {
+      0.0
+    } else {
+      100.0 * self.games_won as f64 / self.games_played as f64
+    }
+  }
+
+  /// Moves per finished game
+  pub fn average_moves(&self) -> f64 {
+    if self.games_played == 0 {
+      0.0
+    } else {
+      self.finished_moves as f64 / self.games_played as f64
+    }
+  }
+
+  pub fn to_text(&self) -> String {
+    let mut text = String::new();
+    writeln!(text, "{} {}", STATISTICS_MAGIC, STATISTICS_VERSION).unwrap();
+    writeln!(text, "games {} {} {}", self.games_played, self.games_won, self.finished_moves).unwrap();
+    writeln!(text, "merges {}", self.merges).unwrap();
+    let directions: Vec<String> = self.direction_moves.iter().map(|c| c.to_string()).collect();
+    writeln!(text, "directions {}", directions.join(" ")).unwrap();
+    for (number, games) in &self.best_tiles {
+      writeln!(text, "tile {} {}", number, games).unwrap();
+    }
+    writeln!(text, "end").unwrap();
+    text
+  }
+
+  pub fn from_text(text: &str) -> io::Result<Self> {
+    let mut lines = Lines::new(text);
+    let header = lines.expect(STATISTICS_MAGIC, Some(1))?;
+    if header[0] != STATISTICS_VERSION.to_string() {
+      return Err(invalid(format!("unsupported statistics version {}", header[0])));
+    }
+
+    let games = lines.expect("games", Some(3))?;
+    let mut result = Statistics {
+      games_played: parse(games[0])?,
+      games_won: parse(games[1])?,
+      finished_moves: parse(games[2])?,
+      merges: parse(lines.expect("merges", Some(1))?[0])?,
+      ..Default::default()
+    };
+    let directions = lines.expect("directions", Some(8))?;
+    for (count, word) in result.direction_moves.iter_mut().zip(directions) {
+      *count = parse(word)?;
+    }
+    while lines.peek_key() == Some("tile") {
+      let tile = lines.expect("tile", Some(2))?;
+      result.best_tiles.insert(parse(tile[0])?, parse(tile[1])?);
+    }
+    lines.expect("end", Some(0))?;
+    Ok(result)
+  }
+
+  pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+    fs::write(path, self.to_text())
+  }
+
+  /// Empty statistics if there is no file yet
+  pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+    match fs::read_to_string(path) {
+      Ok(text) => Self::from_text(&text),
+      Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Statistics::default()),
+      Err(e) => Err(e),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::direction::DirectionSet;
+  use crate::game::play_in_turn;
+  use crate::rules::Rules;
+
+  fn played() -> Statistics {
+    let mut statistics = Statistics::default();
+    let rules = Rules {directions: DirectionSet::ORTHOGONAL, ..Default::default()};
+    let mut game = Game::new(3, 3, rules, 4);
+    play_in_turn(&mut game, usize::MAX, |dir, events| statistics.record_move(dir, events));
+    statistics.record_game(&game);
+    statistics
+  }
+
+  #[test]
+  fn counts_moves_and_games() {
+    let statistics = played();
+    assert_eq!(statistics.games_played, 1);
+    assert_eq!(statistics.direction_moves.iter().sum::<u64>(), statistics.finished_moves);
+    assert_eq!(statistics.direction_moves[Direction::UpLeft.get_index()], 0);
+    assert!(statistics.merges > 0);
+    assert_eq!(statistics.best_tiles.values().sum::<u64>(), 1);
+    assert_eq!(statistics.average_moves(), statistics.finished_moves as f64);
+    assert_eq!(Statistics::default().win_rate(), 0.0);
+  }
+
+  #[test]
+  fn text_round_trip() {
+    let statistics = played();
+    let text = statistics.to_text();
+    assert!(text.starts_with("2048-statistics 1\ngames 1 0 "));
+    assert_eq!(Statistics::from_text(&text).unwrap(), statistics);
+    assert!(Statistics::from_text(&text.replace("merges", "merged")).is_err());
+    assert!(Statistics::from_text(&text.replace("\nend", "")).is_err());
+  }
+}
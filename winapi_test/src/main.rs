@@ -2,157 +2,271 @@
 
 extern crate window;
 extern crate application;
+extern crate game;
+
+use game::ai::{best_of, Agent};
+use game::ai::expectimax::Expectimax;
+use game::args::CommandLine;
+use game::direction::Direction;
+use game::game::Game;
+use game::notation;
+use game::replay::{Playback, Replay};
+use game::save;
+use game::scores::{self, HighScores, ScoreEntry};
+use game::statistics::Statistics;
+
+/// What is drawn over the field
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Screen {
+  Game,
+  HighScores,
+  Statistics,
+  Keys,
+}
 
-use rand::Rng;
-use rand::rngs::ThreadRng;
-use rand::thread_rng;
-use smallvec::SmallVec;
-use std::cmp::{min, max};
-
-const SIZE : usize = 4;
-
-#[derive(Debug, Default)]
-struct Field {
-  numbers: [[i32; SIZE]; SIZE],
-  game_over: bool,
+struct Application2048 {
+  game: Game,
+  agent: Expectimax,
+  /// Values of the moves from the agent, shown until the field changes
+  hint: Option<Vec<(Direction, f64)>>,
+  /// The agent plays a move every `autoplay_interval` milliseconds
+  autoplay: bool,
+  autoplay_interval: u32,
+  /// Result of the last save or load, shown until the next key
+  message: Option<String>,
+  /// Replay shown instead of the game until L
+  playback: Option<Playback>,
+  screen: Screen,
+  high_scores: HighScores,
+  statistics: Statistics,
+  /// Since when the play time is not counted into the game yet
+  started: std::time::Instant,
+  /// The current game is over and in the high scores if it was good enough
+  finished: bool,
+  /// Place of the finished game in the high scores
+  rank: Option<usize>,
 }
 
-impl Field {
-  fn new() -> Self {
-    return Field::default();
+/// The game is saved here on exit and on F5, and loaded from here on start and on F9
+const SAVE_FILE: &str = "2048.sav";
+/// A save that can't be loaded is moved here, so that the next save doesn't overwrite it
+const BAD_SAVE_FILE: &str = "2048.sav.bad";
+/// The saved game is moved here when the command line starts another one, which is saved on exit
+const BACKUP_SAVE_FILE: &str = "2048.sav.bak";
+/// Every game is recorded here as SEED.replay
+const REPLAY_DIR: &str = "replays";
+/// The best finished games
+const SCORES_FILE: &str = "2048.scores";
+/// Totals over all games, saved after every game and on exit
+const STATISTICS_FILE: &str = "2048.stats";
+
+const DEFAULT_AUTOPLAY_INTERVAL: u32 = 200;
+const MIN_AUTOPLAY_INTERVAL: u32 = 10;
+const MAX_AUTOPLAY_INTERVAL: u32 = 2000;
+/// Moves the agent looks ahead for a hint or an autoplay move when there is time
+const AGENT_DEPTH: usize = 3;
+/// Time a hint or an autoplay move may take, the window doesn't respond meanwhile
+const AGENT_TIME: std::time::Duration = std::time::Duration::from_millis(100);
+
+impl Application2048 {
+  fn new(game: Game) -> Self {
+    let mut app = Application2048 {
+      game,
+      agent: Expectimax {depth: AGENT_DEPTH, time_limit: Some(AGENT_TIME), ..Default::default()},
+      hint: None,
+      autoplay: false,
+      autoplay_interval: DEFAULT_AUTOPLAY_INTERVAL,
+      message: None,
+      playback: None,
+      screen: Screen::Game,
+      high_scores: HighScores::load(SCORES_FILE, scores::DEFAULT_TABLE_SIZE).unwrap_or_default(),
+      statistics: Statistics::load(STATISTICS_FILE).unwrap_or_default(),
+      started: std::time::Instant::now(),
+      finished: false,
+      rank: None,
+    };
+    app.reset_game_state();
+    app
   }
 
-  fn new_game(&mut self, rng: &mut impl Rng) {
-    *self = Self::new();
-    self.add_item(rng);
-    self.add_item(rng);
+  /// Resets what belongs to one game after the game was replaced
+  fn reset_game_state(&mut self) {
+    self.started = std::time::Instant::now();
+    self.finished = self.game.get_field().is_game_over();
+    self.rank = None;
   }
 
-  fn fail(&self) -> bool {
-    for i in 0 .. self.numbers.len() {
-      for j in 0 .. self.numbers[i].len() {
-        if self.numbers[i][j] == 0 {
-          return false;
-        }
+  /// Adds the time since the last call to the play time of the game
+  fn count_play_time(&mut self) {
+    let now = std::time::Instant::now();
+    self.game.add_play_time(now - self.started);
+    self.started = now;
+  }
 
-        for i2 in max(i, 1) - 1 .. min(i + 2, self.numbers.len()) {
-          for j2 in max(j, 1) - 1 .. min(j + 2, self.numbers[i2].len()) {
-            if (i != i2 || j != j2) && self.numbers[i][j] == self.numbers[i2][j2] {
-              return false;
-            }
-          }
-        }
+  /// Puts the game that just ended into the high scores, once.
+  /// Games with autoplay moves only count in the statistics
+  fn finish_game(&mut self) {
+    if self.finished || self.game.get_moves().is_empty() {
+      return;
+    }
+    self.finished = true;
+    self.count_play_time();
+    if !self.game.is_assisted() {
+      let date = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+      self.rank = self.high_scores.add(ScoreEntry::from_game(&self.game, date));
+      if self.rank.is_some() {
+        let _ = self.high_scores.save(SCORES_FILE);
       }
     }
+    self.statistics.record_game(&self.game);
+    let _ = self.statistics.save(STATISTICS_FILE);
+  }
 
-    return true;
+  /// Makes the move and counts it in the statistics
+  fn make_move(&mut self, dir: Direction) -> bool {
+    let events = self.game.make_move(dir);
+    self.statistics.record_move(dir, &events);
+    !events.is_empty()
   }
 
-  fn add_item(&mut self, rng: &mut impl Rng) {
-    let mut empty_fields = SmallVec::<[(usize, usize); SIZE * SIZE]>::new();
-    for i in 0 .. self.numbers.len() {
-      for j in 0 .. self.numbers[i].len() {
-        if self.numbers[i][j] == 0 {
-          empty_fields.push((i, j));
-        }
-      }
+  fn high_score_lines(&self) -> Vec<String> {
+    let mut lines = vec!["High scores: score, best tile, moves, time, date, variant".to_string()];
+    for (i, e) in self.high_scores.get_entries().iter().enumerate() {
+      lines.push(format!(
+        "{}. {}  {}  {} moves  {}  {}  {}",
+        i + 1, e.score, e.max_number, e.moves, scores::format_duration(e.duration),
+        scores::format_date(e.date), e.variant
+      ));
     }
-
-    if empty_fields.len() == 0 {
-      self.game_over = true;
-    }
-
-    let f = rng.gen_range(0 .. empty_fields.len());
-    let y = empty_fields[f].0;
-    let x = empty_fields[f].1;
-    let start_numbers = [2, 4];
-    let new_number = start_numbers[rng.gen_range(0 .. start_numbers.len())];
-    self.numbers[y][x] = new_number;
-
-    if self.fail() {
-      self.game_over = true;
+    if self.high_scores.get_entries().is_empty() {
+      lines.push("No games yet".to_string());
     }
+    lines.push("T - back".to_string());
+    lines
   }
 
-  fn push_dir(&mut self, dx: i32, dy: i32) -> bool {
-    let mut lines = SmallVec::<
-      [SmallVec::<[(usize, usize); SIZE]>; SIZE * 2]
-    >::new();
-
-    fn valid(x: i32, y: i32) -> bool {
-      x >= 0 && x < SIZE as i32 && y >= 0 && y < SIZE as i32
-    }
-
-    fn good_number(i: i32) -> bool {
-      i & (i - 1) == 0
+  fn statistics_lines(&self) -> Vec<String> {
+    let s = &self.statistics;
+    let mut lines = vec![
+      "Statistics".to_string(),
+      format!("Games {}, won {} ({:.1}%)", s.games_played, s.games_won, s.win_rate()),
+      format!("Average game {:.0} moves, {} merges in all", s.average_moves(), s.merges),
+    ];
+    let directions: Vec<String> = Direction::ALL.iter()
+      .filter(|d| s.direction_moves[d.get_index()] > 0)
+      .map(|d| format!("{} {}", d.get_digit(), s.direction_moves[d.get_index()]))
+      .collect();
+    // Four items a line, so that the lines stay short
+    if !directions.is_empty() {
+      lines.push("Moves by numpad key".to_string());
+      lines.extend(directions.chunks(4).map(|chunk| chunk.join(", ")));
     }
-
-    for y in 0 .. self.numbers.len() as i32 {
-      for x in 0 .. self.numbers[y as usize].len() as i32 {
-        if !valid(x + dx, y + dy) {
-          lines.push(Default::default());
-          let last_line = lines.last_mut().unwrap();
-          let mut cur_x = x;
-          let mut cur_y = y;
-          while valid(cur_x, cur_y) {
-            last_line.push((cur_y as usize, cur_x as usize));
-            cur_x -= dx;
-            cur_y -= dy;
-          }
-        }
-      }
+    let tiles: Vec<String> = s.best_tiles.iter().rev().map(|(n, games)| format!("{} x{}", n, games)).collect();
+    if !tiles.is_empty() {
+      lines.push("Best tiles".to_string());
+      lines.extend(tiles.chunks(4).map(|chunk| chunk.join(", ")));
     }
+    lines.push("I - back".to_string());
+    lines
+  }
 
-    let mut result = false;
-
-    for l in &lines {
-      let mut i = 0;
-      let mut j = 1;
-      while i < l.len() {
-        while j == i || (j < l.len() && self.numbers[l[j].0][l[j].1] == 0) {
-          j += 1;
-        }
-
-        if j == l.len() {
-          break;
-        }
-
-        if self.numbers[l[i].0][l[i].1] == 0 {
-          result = true;
-          self.numbers[l[i].0][l[i].1] = self.numbers[l[j].0][l[j].1];
-          self.numbers[l[j].0][l[j].1] = 0;
-          continue;
-        }
-
-        let possible_number = 
-          self.numbers[l[j].0][l[j].1] + self.numbers[l[i].0][l[i].1];
-
-        if good_number(possible_number) {
-          result = true;
-          self.numbers[l[i].0][l[i].1] = possible_number;
-          self.numbers[l[j].0][l[j].1] = 0;
-        }
+  fn key_lines(&self) -> Vec<String> {
+    [
+      "Keys",
+      "Numpad or Q W E A D Z X C - move",
+      "U - undo, R - redo",
+      "H - hint, P - autoplay, +/- - speed",
+      "L - replay, K - keep going",
+      "T - high scores, I - statistics",
+      "F5 - save, F9 - load, SPACE - new game",
+      "F1 - back",
+    ].iter().map(|l| l.to_string()).collect()
+  }
 
-        i += 1;
-      }
+  /// Writes the replay of the current game, the file of its seed is replaced
+  fn record_replay(&self) {
+    if self.game.get_moves().is_empty() {
+      return;
     }
+    let path = std::path::Path::new(REPLAY_DIR).join(format!("{}.replay", self.game.get_seed()));
+    let _ = std::fs::create_dir_all(REPLAY_DIR)
+      .and_then(|_| Replay::from_game(&self.game).save(path));
+  }
 
-    result
+  fn on_playback_key(&mut self, key_code: window::KeyCode, must_repaint: &mut bool) {
+    let playback = match self.playback.as_mut() {
+      Some(playback) => playback,
+      None => return,
+    };
+    match key_code {
+      window::KEY_LEFT => *must_repaint = playback.step_back(),
+      window::KEY_RIGHT => *must_repaint = !playback.step_forward().is_empty(),
+      window::KEY_HOME => {
+        playback.seek(0);
+        *must_repaint = true;
+      },
+      window::KEY_END => {
+        playback.seek(usize::MAX);
+        *must_repaint = true;
+      },
+      window::KEY_L | window::KEY_ESCAPE => {
+        self.playback = None;
+        *must_repaint = true;
+      },
+      _ => {}
+    }
   }
 }
 
-#[derive(Default)]
-struct Application2048 {
-  field: Field,
-  rng: ThreadRng,
+fn key_direction(key_code: window::KeyCode) -> Option<Direction> {
+  match key_code {
+    window::KEY_NUMPAD7 | window::KEY_Q => Some(Direction::UpLeft),
+    window::KEY_NUMPAD8 | window::KEY_W => Some(Direction::Up),
+    window::KEY_NUMPAD9 | window::KEY_E => Some(Direction::UpRight),
+    window::KEY_NUMPAD4 | window::KEY_A => Some(Direction::Left),
+    window::KEY_NUMPAD6 | window::KEY_D => Some(Direction::Right),
+    window::KEY_NUMPAD1 | window::KEY_Z => Some(Direction::DownLeft),
+    window::KEY_NUMPAD2 | window::KEY_X => Some(Direction::Down),
+    window::KEY_NUMPAD3 | window::KEY_C => Some(Direction::DownRight),
+    _ => None,
+  }
 }
 
-impl Application2048 {
-  fn new() -> Self {
-    let mut rng =  thread_rng();
-    let mut field = Field::new();
-    field.new_game(&mut rng);
-    Application2048 {rng, field,}
+const KEY_LABELS: [(Direction, &str); 8] = [
+  (Direction::UpLeft, "Q"), (Direction::Up, "W"), (Direction::UpRight, "E"),
+  (Direction::Left, "A"), (Direction::Right, "D"),
+  (Direction::DownLeft, "Z"), (Direction::Down, "X"), (Direction::DownRight, "C"),
+];
+
+/// Dims the picture and writes `lines` over it, small enough for the longest line to fit
+fn draw_overlay(
+  dst: &mut application::image::ImageViewMut<u32>,
+  font_factory: &mut window::AppFontFactory,
+  lines: &[&str],
+) {
+  let size = dst.get_size();
+  // Characters of Arial are about half as wide as they are high
+  let longest = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+  let font_size = std::cmp::min(size.1 / (lines.len() * 2 + 4), size.0 * 2 / (longest + 4));
+  let font_black = font_factory.new_font(
+    "Arial", font_size, 0,
+    application::font::TextLayoutHorizontal::MIDDLE,
+    application::font::TextLayoutVertical::MIDDLE
+  );
+  let font_white = font_factory.new_font(
+    "Arial", font_size, 0x00FFFFFF,
+    application::font::TextLayoutHorizontal::MIDDLE,
+    application::font::TextLayoutVertical::MIDDLE
+  );
+
+  dst.fill(|p| *p = (*p & 0xFCFCFCFC) >> 2);
+  let shift = size.1 / 128 + 1;
+  for (i, line) in lines.iter().enumerate() {
+    let center = (size.0 / 2, size.1 * (i + 2) / (lines.len() + 3));
+    font_black.draw(line, (center.0 + shift, center.1 + shift), dst);
+    font_white.draw(line, center, dst);
   }
 }
 
@@ -163,63 +277,167 @@ impl window::Application for Application2048 {
     must_repaint: &mut bool,
     _must_close: &mut bool
   ) {
+    if self.playback.is_some() {
+      self.on_playback_key(key_code, must_repaint);
+      return;
+    }
+    if self.screen != Screen::Game {
+      if key_code == window::KEY_T || key_code == window::KEY_I || key_code == window::KEY_F1
+        || key_code == window::KEY_ESCAPE {
+        self.screen = Screen::Game;
+        *must_repaint = true;
+      }
+      return;
+    }
+
+    let hint_key = key_code == window::KEY_H;
+    if self.message.take().is_some() {
+      *must_repaint = true;
+    }
     match key_code {
-      window::KEY_SPACE => {
-        if self.field.game_over {
-          self.field.new_game(&mut self.rng);
-          *must_repaint = true;
+      window::KEY_P => {
+        self.autoplay = !self.autoplay && !self.game.get_field().is_stopped();
+        if self.autoplay {
+          self.game.set_assisted();
         }
+        *must_repaint = true;
       },
-      window::KEY_NUMPAD1 | window::KEY_Z  => {
-        if self.field.push_dir(-1, 1) {
-          self.field.add_item(&mut self.rng); 
-          *must_repaint = true;
-        }
-      }
-      window::KEY_NUMPAD2 | window::KEY_X => {
-        if self.field.push_dir(0, 1) {
-          self.field.add_item(&mut self.rng); 
+      window::KEY_ADD | window::KEY_PLUS => {
+        self.autoplay_interval = (self.autoplay_interval / 2).max(MIN_AUTOPLAY_INTERVAL);
+        *must_repaint = true;
+      },
+      window::KEY_SUBTRACT | window::KEY_MINUS => {
+        self.autoplay_interval = (self.autoplay_interval * 2).min(MAX_AUTOPLAY_INTERVAL);
+        *must_repaint = true;
+      },
+      // Any other key only stops the autoplay
+      _ if self.autoplay => {
+        self.autoplay = false;
+        *must_repaint = true;
+      },
+      window::KEY_SPACE => {
+        if self.game.get_field().is_stopped() {
+          self.record_replay();
+          self.finish_game();
+          self.game.new_game(rand::random());
+          self.reset_game_state();
           *must_repaint = true;
         }
-      }
-      window::KEY_NUMPAD3 | window::KEY_C => {
-        if self.field.push_dir(1, 1) {
-          self.field.add_item(&mut self.rng); 
+      },
+      window::KEY_F5 => {
+        self.count_play_time();
+        self.message = Some(match save::save_game(&self.game, SAVE_FILE) {
+          Ok(()) => format!("Saved to {}", SAVE_FILE),
+          Err(e) => format!("Cannot save: {}", e),
+        });
+        *must_repaint = true;
+      },
+      window::KEY_F9 => {
+        self.message = Some(match save::load_game(SAVE_FILE) {
+          Ok(game) => {
+            self.record_replay();
+            self.game = game;
+            self.reset_game_state();
+            format!("Loaded {}", SAVE_FILE)
+          }
+          Err(e) => format!("Cannot load: {}", e),
+        });
+        *must_repaint = true;
+      },
+      window::KEY_U => {
+        // A game in the high scores and statistics is over for good, undo would let it end twice
+        if self.finished {
+          self.message = Some("The game is recorded, SPACE - new game".to_string());
           *must_repaint = true;
-        }
-      }
-      window::KEY_NUMPAD4 | window::KEY_A => {
-        if self.field.push_dir(-1, 0) {
-          self.field.add_item(&mut self.rng); 
+        } else if self.game.undo() {
           *must_repaint = true;
         }
-      }
-      window::KEY_NUMPAD6 | window::KEY_D => {
-        if self.field.push_dir(1, 0) {
-          self.field.add_item(&mut self.rng); 
+      },
+      window::KEY_R => {
+        if self.game.redo() {
           *must_repaint = true;
         }
-      }
-      window::KEY_NUMPAD7 | window::KEY_Q => {
-        if self.field.push_dir(-1, -1) {
-          self.field.add_item(&mut self.rng); 
+      },
+      window::KEY_H => {
+        if !self.game.get_field().is_stopped() {
+          self.hint = Some(self.agent.evaluate(self.game.get_field()));
           *must_repaint = true;
         }
-      }
-      window::KEY_NUMPAD8 | window::KEY_W => {
-        if self.field.push_dir(0, -1) {
-          self.field.add_item(&mut self.rng);
+      },
+      window::KEY_T => {
+        self.screen = Screen::HighScores;
+        *must_repaint = true;
+      },
+      window::KEY_I => {
+        self.screen = Screen::Statistics;
+        *must_repaint = true;
+      },
+      window::KEY_F1 => {
+        self.screen = Screen::Keys;
+        *must_repaint = true;
+      },
+      window::KEY_L => {
+        self.playback = Some(Playback::new(Replay::from_game(&self.game)));
+        *must_repaint = true;
+      },
+      window::KEY_K => {
+        if self.game.get_field().is_won() && !self.game.get_field().is_keep_going() {
+          self.game.keep_going();
           *must_repaint = true;
         }
-      }
-      window::KEY_NUMPAD9 | window::KEY_E => {
-        if self.field.push_dir(1, -1) {
-          self.field.add_item(&mut self.rng);
-          *must_repaint = true;
+      },
+      key_code => {
+        if let Some(dir) = key_direction(key_code) {
+          if self.make_move(dir) {
+            *must_repaint = true;
+            if self.game.get_field().is_game_over() {
+              self.record_replay();
+              self.finish_game();
+            }
+          }
         }
       }
-      _ => {}
     }
+
+    if *must_repaint && !hint_key {
+      self.hint = None;
+    }
+  }
+
+  fn get_timer_interval(&self) -> Option<u32> {
+    if self.autoplay { Some(self.autoplay_interval) } else { None }
+  }
+
+  fn on_timer(&mut self, must_repaint: &mut bool, _must_close: &mut bool) {
+    if !self.autoplay {
+      return;
+    }
+
+    // A move takes no longer than the interval between moves, so the keys still get through
+    let interval = std::time::Duration::from_millis(self.autoplay_interval as u64);
+    self.agent.time_limit = Some(AGENT_TIME.min(interval));
+    let best_move = self.agent.best_move(self.game.get_field());
+    self.agent.time_limit = Some(AGENT_TIME);
+    match best_move {
+      Some(dir) => { self.make_move(dir); },
+      None => self.autoplay = false,
+    }
+    if self.game.get_field().is_stopped() {
+      self.autoplay = false;
+    }
+    if self.game.get_field().is_game_over() {
+      self.record_replay();
+      self.finish_game();
+    }
+    self.hint = None;
+    *must_repaint = true;
+  }
+
+  fn on_close(&mut self) {
+    self.count_play_time();
+    self.record_replay();
+    let _ = save::save_game(&self.game, SAVE_FILE);
+    let _ = self.statistics.save(STATISTICS_FILE);
   }
 
   fn on_paint(
@@ -229,7 +447,13 @@ impl window::Application for Application2048 {
   ) {
     dst.fill(|p| *p = 0);
     let size = dst.get_size();
-    let font_size = size.1 / 16;
+    let game = match &self.playback {
+      Some(playback) => playback.get_game(),
+      None => &self.game,
+    };
+    let field_size = game.get_field().get_size();
+    let grid = ((field_size.0 + 2) * 32, (field_size.1 + 2) * 32);
+    let font_size = std::cmp::min(size.0 / (field_size.0 + 2), size.1 / (field_size.1 + 2)) * 3 / 8;
     let font_black = font_factory.new_font(
       "Arial", font_size, 0,
       application::font::TextLayoutHorizontal::MIDDLE,
@@ -246,10 +470,12 @@ impl window::Application for Application2048 {
       0x0000C0, 0x0060C0, 0x00C0C0, 0x00C060, 0x00C000, 0x60C000, 0xC0C000, 0xC06000, 0xC00000,
     ];
 
-    for y in 0 .. SIZE {
-      for x in 0 .. SIZE {
-        let n = self.field.numbers[y][x];
-        let color = if n == 0 {
+    for y in 0 .. field_size.1 {
+      for x in 0 .. field_size.0 {
+        let n = game.get_field().get_numbers()[y][x];
+        let color = if game.get_field().get_rules().is_obstacle((x, y)) {
+          0x404040
+        } else if n == 0 {
           0
         } else {
           let mut n = n;
@@ -260,8 +486,8 @@ impl window::Application for Application2048 {
           } colors[log2 % colors.len()]
         };
         let mut w = dst.window_mut(
-          (size.0 * (x * 32 + 33) / ((SIZE + 2) * 32), size.1 * (y * 32 + 33) / ((SIZE + 2) * 32)),
-          (size.0 * (x * 32 + 63) / ((SIZE + 2) * 32), size.1 * (y * 32 + 63) / ((SIZE + 2) * 32))
+          (size.0 * (x * 32 + 33) / grid.0, size.1 * (y * 32 + 33) / grid.1),
+          (size.0 * (x * 32 + 63) / grid.0, size.1 * (y * 32 + 63) / grid.1)
         );
         w.fill(|p| *p = color);
 
@@ -275,63 +501,181 @@ impl window::Application for Application2048 {
       }
     }
 
-    if self.field.game_over {
-      let font_size = size.1 / 8;
-      let font_black = font_factory.new_font(
-        "Arial", font_size, 0,
+    let field = game.get_field();
+    let font_panel = font_factory.new_font(
+      "Arial", font_size * 2 / 3, 0x00C0C0C0,
+      application::font::TextLayoutHorizontal::MIDDLE,
+      application::font::TextLayoutVertical::MIDDLE
+    );
+    let top = size.1 * 16 / grid.1;
+    let bottom = size.1 - top;
+    font_panel.draw(&format!("Score {}", field.get_score()), (size.0 * 3 / 10, top), dst);
+    font_panel.draw(&format!("Best {}", field.get_best_score()), (size.0 * 7 / 10, top), dst);
+    let font_small = font_factory.new_font(
+      "Arial", font_size / 2, 0x00C0C0C0,
+      application::font::TextLayoutHorizontal::MIDDLE,
+      application::font::TextLayoutVertical::MIDDLE
+    );
+    let status = match &self.message {
+      Some(message) => message.clone(),
+      None => format!("Seed {}", game.get_seed()),
+    };
+    font_small.draw(&status, (size.0 * 3 / 10, bottom), dst);
+    if let Some(playback) = &self.playback {
+      let status = format!(
+        "Replay {} / {}, LEFT/RIGHT - step, L - exit",
+        playback.get_position(), playback.get_replay().moves.len()
+      );
+      font_small.draw(&status, (size.0 * 7 / 10, bottom), dst);
+    } else if self.autoplay {
+      let status = format!("Autoplay, {} ms, +/- speed", self.autoplay_interval);
+      font_small.draw(&status, (size.0 * 7 / 10, bottom), dst);
+    } else {
+      font_small.draw("F1 - keys", (size.0 * 7 / 10, bottom), dst);
+    }
+
+    if self.screen != Screen::Game {
+      let lines = match self.screen {
+        Screen::Statistics => self.statistics_lines(),
+        Screen::Keys => self.key_lines(),
+        _ => self.high_score_lines(),
+      };
+      let lines: Vec<&str> = lines.iter().map(|l| l.as_str()).collect();
+      draw_overlay(dst, font_factory, &lines);
+    } else if self.playback.is_some() {
+      // The replay shows just the field
+    } else if field.is_won() && !field.is_keep_going() {
+      draw_overlay(dst, font_factory, &["You win!", "K - keep going", "SPACE - new game"]);
+    } else if field.is_game_over() {
+      let result = match self.rank {
+        Some(rank) => format!("New high score #{}!", rank + 1),
+        None => format!("Score {}", field.get_score()),
+      };
+      draw_overlay(dst, font_factory, &["Game over", &result, "T - high scores", "Press SPACE"]);
+    } else {
+      let left = size.0 * 16 / grid.0;
+      let right = size.0 - left;
+      let font_grey = font_factory.new_font(
+        "Arial", font_size, 0x00404040,
         application::font::TextLayoutHorizontal::MIDDLE,
         application::font::TextLayoutVertical::MIDDLE
       );
-      let font_white = font_factory.new_font(
-        "Arial", font_size, 0x00FFFFFF,
+      let font_hint = font_factory.new_font(
+        "Arial", font_size, 0x00FFFF00,
         application::font::TextLayoutHorizontal::MIDDLE,
         application::font::TextLayoutVertical::MIDDLE
       );
+      let best_move = self.hint.as_ref().and_then(|hint| best_of(hint));
+      let directions = field.get_rules().directions;
+      let legal_moves = field.legal_moves();
+      for (dir, label) in KEY_LABELS.iter().filter(|(dir, _)| directions.contains(*dir)) {
+        let (dx, dy) = dir.get_delta();
+        let x = [left, size.0 / 2, right][(dx + 1) as usize];
+        let y = [top, size.1 / 2, bottom][(dy + 1) as usize];
+        let font = if best_move == Some(*dir) {
+          &font_hint
+        } else if legal_moves.contains(*dir) {
+          &font_white
+        } else {
+          &font_grey
+        };
+        font.draw(label, (x, y), dst);
 
-      dst.fill(|p| *p = (*p & 0xFCFCFCFC) >> 2);
-      let center = (dst.get_size().0 / 2, dst.get_size().1 * 2 / 5);
-      let shift = size.1 / 128 + 1;
-      font_black.draw("Game over", (center.0 + shift, center.1 + shift), dst);
-      font_white.draw("Game over", center, dst);
-
-      let center = (dst.get_size().0 / 2, dst.get_size().1 * 3 / 5);
-      let shift = size.1 / 128 + 1;
-      font_black.draw("Press SPACE", (center.0 + shift, center.1 + shift), dst);
-      font_white.draw("Press SPACE", center, dst);
-    } else {
-      let center = (dst.get_size().0 / 12, dst.get_size().1 / 12);
-      font_white.draw("Q", center, dst);
-      let center = (dst.get_size().0 / 2, dst.get_size().1 / 12);
-      font_white.draw("W", center, dst);
-      let center = (dst.get_size().0 * 11 / 12, dst.get_size().1 / 12);
-      font_white.draw("E", center, dst);
-      let center = (dst.get_size().0 / 12, dst.get_size().1 / 2);
-      font_white.draw("A", center, dst);
-      let center = (dst.get_size().0 * 11 / 12, dst.get_size().1 / 2);
-      font_white.draw("D", center, dst);
-      let center = (dst.get_size().0 / 12, dst.get_size().1 * 11  / 12);
-      font_white.draw("Z", center, dst);
-      let center = (dst.get_size().0 / 2, dst.get_size().1 * 11  / 12);
-      font_white.draw("X", center, dst);
-      let center = (dst.get_size().0 * 11 / 12, dst.get_size().1 * 11  / 12);
-      font_white.draw("C", center, dst);
+        // The value goes between the label and the board
+        let value = self.hint.iter().flatten().find(|(d, _)| d == dir).map(|(_, v)| v);
+        if let Some(value) = value {
+          let value_y = if dy > 0 { y - font_size * 3 / 4 } else { y + font_size * 3 / 4 };
+          font_small.draw(&format!("{:.1}", value), (x, value_y), dst);
+        }
+      }
     }
   }
 }
 
+/// Options of the game itself, everything else must be a size or a rule option
+const OPTIONS: [&str; 3] = ["seed=", "replay=", "position="];
+
+/// Ends the game with an error. The GUI has no console, so it is shown in a message box
+fn fail(message: &str) -> ! {
+  window::show_error(message);
+  std::process::exit(1)
+}
+
 fn main() {
-  window::run_application(&mut Application2048::new())
+  // Optional command line: winapi_test [width [height]] [pow2sum|classic|fibonacci|threes]
+  //   [all|orthogonal|diagonal] [target=N|target=none]
+  //   [obstacles=X:Y,...|level=corner|diagonal|ring|gates] [wrap]
+  //   [spawn=random|corners|away] [tiles=N] [weights=NUMBER:WEIGHT,...] [seed=N] [replay=FILE]
+  //   ["position=4x4 .2../..../.4../.... p 0"]
+  // Without other arguments than replay=FILE the saved game goes on, the replay is shown over it.
+  // Position starts from a position in the notation of game::notation
+  let args: Vec<String> = std::env::args().skip(1).collect();
+  let only_replay = args.iter().all(|a| a.starts_with("replay="));
+  let command_line = CommandLine::parse(args, &OPTIONS, true).unwrap_or_else(|e| fail(&e));
+  let replay = command_line.get_option("replay=").map(
+    |path| Replay::load(path).unwrap_or_else(|e| fail(&format!("Cannot load {}: {}", path, e)))
+  );
+  let (game, message) = if only_replay {
+    let (saved, message) = load_saved_game();
+    (saved.map_or_else(|| new_game(&command_line), Ok), message)
+  } else {
+    let game = new_game(&command_line);
+    (game, back_up_save())
+  };
+  let game = game.unwrap_or_else(|e| fail(&e));
+
+  let mut application = Application2048::new(game);
+  application.message = message;
+  application.playback = replay.map(Playback::new);
+  window::run_application(&mut application)
 }
 
+/// Moves the saved game away before a game from the command line replaces it on exit
+fn back_up_save() -> Option<String> {
+  if !std::path::Path::new(SAVE_FILE).exists() {
+    return None;
+  }
+  match std::fs::rename(SAVE_FILE, BACKUP_SAVE_FILE) {
+    Ok(()) => Some(format!("The saved game is moved to {}", BACKUP_SAVE_FILE)),
+    Err(e) => fail(&format!("Cannot back up {}: {}", SAVE_FILE, e)),
+  }
+}
 
-#[cfg(test)]
-mod tests {
-  use super::*;
+/// The saved game, `None` if there is none. A save that can't be loaded is moved away,
+/// the message tells where
+fn load_saved_game() -> (Option<Game>, Option<String>) {
+  match save::load_game(SAVE_FILE) {
+    Ok(game) => (Some(game), None),
+    Err(e) if e.kind() == std::io::ErrorKind::NotFound => (None, None),
+    Err(e) => {
+      if std::fs::rename(SAVE_FILE, BAD_SAVE_FILE).is_err() {
+        fail(&format!("Cannot load {}: {}", SAVE_FILE, e));
+      }
+      (None, Some(format!("Cannot load {}: {}, moved to {}", SAVE_FILE, e, BAD_SAVE_FILE)))
+    }
+  }
+}
 
-  #[test]
-  fn fiend_is_fail() {
-    let mut field = Field::new();
-    field.numbers = [[4, 32, 2, 4], [2, 32, 16, 4], [8, 4, 16, 32], [2, 4, 2, 8]];
-    assert!(!field.fail());
+/// A game of the size, rules and start of the command line
+fn new_game(command_line: &CommandLine) -> Result<Game, String> {
+  let (width, height) = command_line.size;
+  let mut rules = command_line.rules.clone();
+  let seed = command_line.parse_option("seed=")?.unwrap_or_else(rand::random);
+  let position = command_line.get_option("position=")
+    .map(|p| notation::read_position(p).map_err(|e| format!("Bad position: {}", e)))
+    .transpose()?;
+  match position {
+    Some((mut field, side)) => {
+      // Obstacles of the position unless the command line has its own
+      if rules.obstacles.is_empty() {
+        rules.obstacles = field.get_rules().obstacles.clone();
+      }
+      field.try_set_rules(rules).map_err(|e| format!("Bad rules: {}", e))?;
+      Ok(notation::start_game(field, side, seed))
+    }
+    None => {
+      rules.validate(width, height).map_err(|e| format!("Bad rules: {}", e))?;
+      Ok(Game::new(width, height, rules, seed))
+    }
   }
-}
\ No newline at end of file
+}
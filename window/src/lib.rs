@@ -28,6 +28,18 @@ pub const KEY_NUMPAD6: KeyCode = KeyCode(VK_NUMPAD6 as usize);
 pub const KEY_NUMPAD7: KeyCode = KeyCode(VK_NUMPAD7 as usize);
 pub const KEY_NUMPAD8: KeyCode = KeyCode(VK_NUMPAD8 as usize);
 pub const KEY_NUMPAD9: KeyCode = KeyCode(VK_NUMPAD9 as usize);
+pub const KEY_ESCAPE: KeyCode = KeyCode(VK_ESCAPE as usize);
+pub const KEY_LEFT: KeyCode = KeyCode(VK_LEFT as usize);
+pub const KEY_RIGHT: KeyCode = KeyCode(VK_RIGHT as usize);
+pub const KEY_HOME: KeyCode = KeyCode(VK_HOME as usize);
+pub const KEY_END: KeyCode = KeyCode(VK_END as usize);
+pub const KEY_F1: KeyCode = KeyCode(VK_F1 as usize);
+pub const KEY_F5: KeyCode = KeyCode(VK_F5 as usize);
+pub const KEY_F9: KeyCode = KeyCode(VK_F9 as usize);
+pub const KEY_ADD: KeyCode = KeyCode(VK_ADD as usize);
+pub const KEY_SUBTRACT: KeyCode = KeyCode(VK_SUBTRACT as usize);
+pub const KEY_PLUS: KeyCode = KeyCode(VK_OEM_PLUS as usize);
+pub const KEY_MINUS: KeyCode = KeyCode(VK_OEM_MINUS as usize);
 
 pub const KEY_A: KeyCode = KeyCode('A' as usize);
 pub const KEY_B: KeyCode = KeyCode('B' as usize);
@@ -67,6 +79,27 @@ pub trait Application {
   );
 
   fn on_paint(&mut self, destination: &mut ImageViewMut<u32>, font_factory: &mut AppFontFactory);
+
+  /// Milliseconds between `on_timer` calls, `None` to stop them. Asked again after every event
+  fn get_timer_interval(&self) -> Option<u32> {
+    None
+  }
+
+  fn on_timer(&mut self, _must_repaint: &mut bool, _must_close: &mut bool) {}
+
+  /// The window is being destroyed, the last chance to save anything
+  fn on_close(&mut self) {}
+}
+
+const TIMER_ID: usize = 1;
+
+fn update_timer<AppImpl: Application>(hwnd: HWND, application: &AppImpl) {
+  unsafe {
+    match application.get_timer_interval() {
+      Some(interval) => { SetTimer(hwnd, TIMER_ID, interval, None); }
+      None => { KillTimer(hwnd, TIMER_ID); }
+    }
+  }
 }
 
 struct Context<'i, AppImpl: Application> {
@@ -103,6 +136,17 @@ pub unsafe extern "system" fn window_proc<AppImpl: Application> (
       if must_repaint {
         InvalidateRect(hwnd, 0 as *const RECT, FALSE);
       }
+      update_timer(hwnd, get_context().application);
+    }
+
+    WM_TIMER => {
+      let mut must_repaint = false;
+      let mut must_close = false;
+      get_context().application.on_timer(&mut must_repaint, &mut must_close);
+      if must_repaint {
+        InvalidateRect(hwnd, 0 as *const RECT, FALSE);
+      }
+      update_timer(hwnd, get_context().application);
     }
 
     WM_PAINT => {
@@ -124,6 +168,7 @@ pub unsafe extern "system" fn window_proc<AppImpl: Application> (
     }
 
     WM_DESTROY => {
+        get_context().application.on_close();
         PostQuitMessage(0);
     }
     _ => { return DefWindowProcW(hwnd, msg, wparam, lparam); }
@@ -182,9 +227,18 @@ fn handle_message( window : HWND ) -> bool {
   }
 }
 
+/// Shows `message` in a message box with an error icon, returns when it is closed
+pub fn show_error(message: &str) {
+  let mut wide_strings = WideStringManager::new();
+  unsafe {
+    MessageBoxW(0 as HWND, wide_strings.from(message), wide_strings.from("2048"), MB_OK | MB_ICONERROR);
+  }
+}
+
 pub fn run_application(application: &mut impl Application) {
   let mut context = Context {application, buffer: None, font_factory: AppFontFactory::new()};
   let window = create_window(&mut context);
+  update_timer(window, context.application);
   loop {
     if !handle_message(window) {
       break;